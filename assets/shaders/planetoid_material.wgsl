@@ -1,17 +1,75 @@
 #import bevy_pbr::mesh_view_bindings
 #import bevy_pbr::mesh_bindings
 #import bevy_pbr::mesh_functions
+#import bevy_pbr::shadows
 
 @group(1) @binding(0)
 var color_map: texture_2d<f32>;
 @group(1) @binding(1)
 var color_map_sampler: sampler;
 @group(1) @binding(2)
-var planetoid_heightmap: texture_2d<f32>;
+var<uniform> occluder: vec4<f32>;
 @group(1) @binding(3)
-var planetoid_heightmap_sampler: sampler;
-@group(1) @binding(4)
-var<uniform> planetoid: vec4<f32>;
+var<uniform> atmosphere: vec4<f32>;
+
+// how much of the sunlight an eclipse takes away at the heart of the shadow
+let ECLIPSE_DARKNESS: f32 = 0.8;
+// brightness at the heart of a shadow cast by something standing on the surface, which is
+// what the terminator gets as well
+let CAST_SHADOW_INTENSITY: f32 = 0.0;
+// width of the twilight band in a one-thick atmosphere, as the sine of the sun's height
+// above the horizon
+let TWILIGHT_WIDTH: f32 = 0.35;
+// how much of the air's color twilight and the rim take on at most
+let TWILIGHT_TINT: f32 = 0.6;
+let RIM_TINT: f32 = 0.8;
+// higher keeps the rim glow closer to the edge of the disc
+let RIM_POWER: f32 = 3.0;
+// steps the glow is cut into, so it bands like the color ramp instead of blending smoothly
+let ATMOSPHERE_STEPS: f32 = 4.0;
+
+// 1.0 where the occluding body fully blocks the sun, fading out towards its edge
+fn eclipse_shadow(position: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+    let radius = occluder.w;
+    if (radius <= 0.0) {
+        return 0.0;
+    }
+    let towards = occluder.xyz - position;
+    let along = dot(towards, light_dir);
+    if (along <= 0.0) {
+        return 0.0;
+    }
+    let miss = length(towards - light_dir * along);
+    return 1.0 - smoothstep(radius * 0.6, radius, miss);
+}
+
+fn banded(amount: f32) -> f32 {
+    return floor(clamp(amount, 0.0, 1.0) * ATMOSPHERE_STEPS) / ATMOSPHERE_STEPS;
+}
+
+// tints the surface with the color of the air: a band of twilight along the terminator, and
+// a glow around the sunlit rim where the view passes through the most air
+fn scatter(color: vec3<f32>, position: vec3<f32>, light_dir: vec3<f32>, eclipse: f32) -> vec3<f32> {
+    let air = atmosphere.w;
+    if (air <= 0.0) {
+        return color;
+    }
+    // the smooth sphere rather than the terrain, so the glow doesn't pick out every bump
+    let up = normalize(position - mesh.model[3].xyz);
+    let sun_height = dot(up, light_dir);
+    let view_dir = normalize(view.world_position.xyz - position);
+    let sunlight = 1.0 - eclipse * ECLIPSE_DARKNESS;
+
+    // thicker air carries the light further round into the night
+    let width = TWILIGHT_WIDTH * air;
+    let twilight = 1.0 - smoothstep(0.0, width, abs(sun_height + width * 0.5));
+    let rim = pow(1.0 - max(dot(up, view_dir), 0.0), RIM_POWER) * air
+        * smoothstep(-width, width, sun_height);
+
+    var tinted = mix(color, atmosphere.rgb, banded(twilight * sunlight) * TWILIGHT_TINT);
+    tinted = mix(tinted, atmosphere.rgb, banded(rim * sunlight) * RIM_TINT);
+    return tinted;
+}
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
@@ -19,39 +77,16 @@ struct VertexOutput {
     @location(1) world_normal: vec4<f32>,
     @location(2) position: vec4<f32>,
     @location(3) normal: vec4<f32>,
-    @location(4) displacement: vec4<f32>,
 };
 
-fn rand2(n: vec2<f32>) -> f32 {
-    return fract(sin(dot(n, vec2<f32>(12.9898, 4.1414))) * 43758.5453);
-}
-
-fn noise2(n: vec2<f32>) -> f32 {
-    let d = vec2<f32>(0., 1.);
-    let b = floor(n);
-    let f = smoothstep(vec2<f32>(0.), vec2<f32>(1.), fract(n));
-    return mix(mix(rand2(b), rand2(b + d.yx), f.x), mix(rand2(b + d.xy), rand2(b + d.yy), f.x), f.y);
-}
-
 @vertex
 fn vertex(
     @location(0) position: vec4<f32>,
     @location(1) normal: vec4<f32>,
 ) -> VertexOutput {
-    var polar_pos = vec2<f32>(
-        atan2(position.z, position.x),
-        atan2(
-            sqrt(position.x * position.x + position.y * position.y),
-            position.z
-        )
-    );
-    var height = noise2(polar_pos * 2.0) * 2.0 - 1.0;
-    var height = height * 2.0;
-    let displacement = vec4<f32>(normalize(position.xyz) * height, height);
-
+    // the terrain is already in the mesh, which is rebuilt from the heightfield on the CPU
     var out: VertexOutput;
-    out.displacement = displacement;
-    out.position = position + vec4<f32>(displacement.xyz * 0.02, 0.0);
+    out.position = position;
     out.normal = normal;
     out.world_normal = vec4<f32>(mesh_normal_local_to_world(normal.xyz), 1.0);
     out.world_position = mesh_position_local_to_world(mesh.model, out.position);
@@ -64,19 +99,24 @@ fn vertex(
 fn fragment(
     vertex: VertexOutput
 ) -> @location(0) vec4<f32> {
-    let sun_pos = planetoid.xyz;
-    let sun_intensity = planetoid.w;
-    let light_dir = normalize(sun_pos - vertex.world_position.xyz);
-    let light_intensity = sun_intensity * dot(light_dir, vertex.world_normal.xyz);
-    let final_color = textureSample(color_map, color_map_sampler, vec2<f32>((light_intensity + 1.0) / 2.0, 0.5));
-    let position = vertex.world_position;
-//    return vec4<f32>(
-//        0.5 + atan2(position.z, position.x) / 6.14,
-//        acos(
-//            position.y / sqrt(position.x * position.x + position.y * position.y + position.z * position.z)
-//        ) / 3.14,
-//        0.0,
-//        1.0
-//    );
+    // the sun is the scene's one directional light
+    if (lights.n_directional_lights == 0u) {
+        return textureSample(color_map, color_map_sampler, vec2<f32>(0.0, 0.5));
+    }
+    let light = lights.directional_lights[0u];
+    let light_dir = light.direction_to_light;
+    let normal = normalize(vertex.world_normal.xyz);
+    var light_intensity = dot(light_dir, normal);
+
+    // creatures, plants and structures shade the ground they stand on
+    if ((light.flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+        let lit = fetch_directional_shadow(0u, vertex.world_position, normal);
+        light_intensity = mix(min(light_intensity, CAST_SHADOW_INTENSITY), light_intensity, lit);
+    }
+    // in an eclipse the day side sinks towards the dark end of the ramp
+    let shadow = eclipse_shadow(vertex.world_position.xyz, light_dir);
+    light_intensity = light_intensity - shadow * ECLIPSE_DARKNESS * (light_intensity + 1.0);
+    let ramp_color = textureSample(color_map, color_map_sampler, vec2<f32>((light_intensity + 1.0) / 2.0, 0.5));
+    let final_color = vec4<f32>(scatter(ramp_color.rgb, vertex.world_position.xyz, light_dir, shadow), ramp_color.a);
     return final_color;
 }
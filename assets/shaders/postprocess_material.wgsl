@@ -12,15 +12,233 @@ var noise_texture: texture_2d<f32>;
 var noise_texture_sampler: sampler;
 @group(1) @binding(4)
 var<uniform> orientation: mat4x4<f32>;
+@group(1) @binding(5)
+var palette_texture: texture_2d<f32>;
+@group(1) @binding(6)
+var palette_texture_sampler: sampler;
+// x: effect id, matching `PostEffect::shader_id`
+@group(1) @binding(7)
+var<uniform> effect: vec4<f32>;
+@group(1) @binding(8)
+var<uniform> params: vec4<f32>;
+@group(1) @binding(9)
+var highlight_texture: texture_2d<f32>;
+@group(1) @binding(10)
+var highlight_texture_sampler: sampler;
+@group(1) @binding(11)
+var hud_texture: texture_2d<f32>;
+@group(1) @binding(12)
+var hud_texture_sampler: sampler;
+
+let PI: f32 = 3.14159265;
+
+fn to_linear(color: vec3<f32>) -> vec3<f32> {
+    return pow(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2));
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.3, 0.59, 0.11));
+}
+
+fn is_background(color: vec3<f32>) -> bool {
+    return color.r == 0.0 && color.g == 0.0 && color.b == 0.0;
+}
+
+fn load_pixel(pixel: vec2<i32>) -> vec4<f32> {
+    let dims = textureDimensions(render_texture);
+    return textureLoad(render_texture, clamp(pixel, vec2<i32>(0), dims - vec2<i32>(1)), 0);
+}
+
+fn uv_to_pixel(uv: vec2<f32>) -> vec2<f32> {
+    return uv * vec2<f32>(textureDimensions(render_texture));
+}
+
+fn to_srgb(color: vec3<f32>) -> vec3<f32> {
+    return pow(max(color, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.2));
+}
+
+fn bayer4(pixel: vec2<i32>) -> f32 {
+    var matrix = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0
+    );
+    let index = (pixel.y & 3) * 4 + (pixel.x & 3);
+    return (matrix[index] + 0.5) / 16.0;
+}
+
+fn blue_noise(pixel: vec2<i32>) -> f32 {
+    let dims = textureDimensions(noise_texture);
+    let coords = ((pixel % dims) + dims) % dims;
+    // the texture is loaded as sRGB, so convert back to get evenly spread thresholds
+    return to_srgb(textureLoad(noise_texture, coords, 0).rgb).r;
+}
+
+// Offsets the dither pattern by the camera's heading around the planetoid, so the
+// pattern sticks to the world while orbiting instead of crawling across it.
+fn world_anchored_pixel(uv: vec2<f32>) -> vec2<i32> {
+    let forward = normalize((orientation * vec4<f32>(0.0, 0.0, -1.0, 0.0)).xyz);
+    let longitude = atan2(forward.z, forward.x);
+    let latitude = asin(clamp(forward.y, -1.0, 1.0));
+    let offset = vec2<f32>(longitude, -latitude) / (2.0 * PI) * params.z;
+    return vec2<i32>(floor(uv_to_pixel(uv) + offset));
+}
+
+fn dither_threshold(pixel: vec2<i32>) -> f32 {
+    if (params.x > 1.5) {
+        return blue_noise(pixel);
+    }
+    return bayer4(pixel);
+}
+
+fn nearest_palette_color(color: vec3<f32>) -> vec4<f32> {
+    let count = textureDimensions(palette_texture).x;
+    var best = textureLoad(palette_texture, vec2<i32>(0, 0), 0);
+    var best_distance = 1000.0;
+    for (var i = 0; i < count; i = i + 1) {
+        let candidate = textureLoad(palette_texture, vec2<i32>(i, 0), 0);
+        let delta = to_srgb(candidate.rgb) - color;
+        // weight green higher, as the eye is most sensitive to it
+        let distance = dot(delta * delta, vec3<f32>(0.3, 0.59, 0.11));
+        if (distance < best_distance) {
+            best_distance = distance;
+            best = candidate;
+        }
+    }
+    return vec4<f32>(best.rgb, 1.0);
+}
+
+// params: x mode (1 ordered, 2 blue noise), y strength, z pixels per revolution
+fn dither_pass(color: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+    if (is_background(color.rgb)) {
+        return color;
+    }
+    let threshold = dither_threshold(world_anchored_pixel(uv));
+    return vec4<f32>(to_linear(to_srgb(color.rgb) + (threshold - 0.5) * params.y), 1.0);
+}
+
+fn palette_pass(color: vec4<f32>) -> vec4<f32> {
+    // space stays pitch black regardless of the palette
+    if (is_background(color.rgb)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return nearest_palette_color(to_srgb(color.rgb));
+}
+
+// params: x darkening, y line period in pixels
+fn scanlines_pass(color: vec4<f32>, pixel: vec2<i32>) -> vec4<f32> {
+    let period = max(i32(params.y), 1);
+    if (pixel.y % period == 0) {
+        return vec4<f32>(color.rgb * (1.0 - params.x), 1.0);
+    }
+    return color;
+}
+
+// params: x strength, y inner radius
+fn vignette_pass(color: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+    let distance = length(uv - vec2<f32>(0.5));
+    let falloff = smoothstep(params.y, 0.75, distance);
+    return vec4<f32>(color.rgb * (1.0 - falloff * params.x), 1.0);
+}
+
+// params: x luminance threshold, y darkening
+fn outline_pass(color: vec4<f32>, pixel: vec2<i32>) -> vec4<f32> {
+    let center = luminance(to_srgb(color.rgb));
+    let right = luminance(to_srgb(load_pixel(pixel + vec2<i32>(1, 0)).rgb));
+    let down = luminance(to_srgb(load_pixel(pixel + vec2<i32>(0, 1)).rgb));
+    // only darken the brighter side of an edge, so outlines stay one pixel wide
+    if (center - min(right, down) > params.x) {
+        return vec4<f32>(color.rgb * (1.0 - params.y), 1.0);
+    }
+    return color;
+}
+
+// params: x luminance threshold, y intensity, z radius in pixels
+fn glow_pass(color: vec4<f32>, pixel: vec2<i32>) -> vec4<f32> {
+    let radius = i32(params.z);
+    var glow = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var y = -radius; y <= radius; y = y + 1) {
+        for (var x = -radius; x <= radius; x = x + 1) {
+            let sample = load_pixel(pixel + vec2<i32>(x, y)).rgb;
+            let falloff = 1.0 / (1.0 + f32(x * x + y * y));
+            glow = glow + sample * step(params.x, luminance(to_srgb(sample))) * falloff;
+            weight = weight + falloff;
+        }
+    }
+    return vec4<f32>(color.rgb + glow / weight * params.y, 1.0);
+}
+
+fn load_highlight(pixel: vec2<i32>) -> vec4<f32> {
+    let dims = textureDimensions(highlight_texture);
+    if (any(pixel < vec2<i32>(0)) || any(pixel >= dims)) {
+        return vec4<f32>(0.0);
+    }
+    return textureLoad(highlight_texture, pixel, 0);
+}
+
+fn has_highlight(mask: vec4<f32>) -> bool {
+    return mask.a > 0.0 && (mask.r + mask.g + mask.b) > 0.0;
+}
+
+// Draws a one pixel outline just outside the silhouettes in the highlight mask,
+// in the color of the silhouette.
+fn highlight_pass(color: vec4<f32>, pixel: vec2<i32>) -> vec4<f32> {
+    if (has_highlight(load_highlight(pixel))) {
+        return color;
+    }
+    var offsets = array<vec2<i32>, 4>(
+        vec2<i32>(1, 0),
+        vec2<i32>(-1, 0),
+        vec2<i32>(0, 1),
+        vec2<i32>(0, -1)
+    );
+    for (var i = 0; i < 4; i = i + 1) {
+        let neighbor = load_highlight(pixel + offsets[i]);
+        if (has_highlight(neighbor)) {
+            return vec4<f32>(neighbor.rgb, 1.0);
+        }
+    }
+    return color;
+}
+
+fn hud_pass(color: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+    let hud = textureSample(hud_texture, hud_texture_sampler, uv);
+    return vec4<f32>(mix(color.rgb, hud.rgb, hud.a), 1.0);
+}
 
 @fragment
 fn fragment(
     #import bevy_pbr::mesh_vertex_output
 ) -> @location(0) vec4<f32> {
-    let color = textureSample(render_texture, render_texture_sampler, uv);
-    let noise = textureSample(noise_texture, noise_texture_sampler, floor(uv * 64.0) / 64.0);
-    if (color.r == 0.0 && color.g == 0.0 && color.b == 0.0) {
-        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    let pixel = vec2<i32>(floor(uv_to_pixel(uv)));
+    let color = load_pixel(pixel);
+    let id = i32(effect.x + 0.5);
+
+    if (id == 1) {
+        return dither_pass(color, uv);
+    }
+    if (id == 2) {
+        return palette_pass(color);
+    }
+    if (id == 3) {
+        return scanlines_pass(color, pixel);
+    }
+    if (id == 4) {
+        return vignette_pass(color, uv);
+    }
+    if (id == 5) {
+        return outline_pass(color, pixel);
+    }
+    if (id == 6) {
+        return glow_pass(color, pixel);
+    }
+    if (id == 7) {
+        return highlight_pass(color, pixel);
+    }
+    if (id == 8) {
+        return hud_pass(color, uv);
     }
     return color;
 }
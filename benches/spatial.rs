@@ -0,0 +1,132 @@
+//! Proximity queries over populations of a few thousand creatures and plants, against the
+//! linear scan they replace.
+
+use bevy::math::Vec3;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+#[allow(dead_code)]
+#[path = "../src/planetoid/spatial.rs"]
+mod spatial;
+
+use spatial::SpatialIndex;
+
+const BANDS: usize = 32;
+const QUERY_RADIUS: f32 = 0.1;
+
+fn random_points(count: usize) -> Vec<Vec3> {
+    let mut rng = SmallRng::seed_from_u64(0);
+    (0..count)
+        .map(|_| {
+            // uniform on the sphere
+            let y: f32 = rng.gen_range(-1.0..1.0);
+            let angle: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+            let ring = (1.0 - y * y).sqrt();
+            Vec3::new(ring * angle.cos(), y, ring * angle.sin())
+        })
+        .collect()
+}
+
+fn build_index(points: &[Vec3]) -> SpatialIndex<usize> {
+    let mut index = SpatialIndex::new(BANDS);
+    for (i, point) in points.iter().enumerate() {
+        index.insert(*point, i);
+    }
+    index
+}
+
+fn rebuild(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rebuild");
+    for count in [1000, 5000, 20000] {
+        let points = random_points(count);
+        let mut index = SpatialIndex::new(BANDS);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &points, |b, points| {
+            b.iter(|| {
+                index.clear();
+                for (i, point) in points.iter().enumerate() {
+                    index.insert(*point, i);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Every point looking for its neighbors, as foraging and mating do each frame.
+fn radius_all(c: &mut Criterion) {
+    let mut group = c.benchmark_group("radius_all");
+    group.sample_size(10);
+    for count in [1000, 5000] {
+        let points = random_points(count);
+        let index = build_index(&points);
+
+        group.bench_with_input(BenchmarkId::new("index", count), &points, |b, points| {
+            b.iter(|| {
+                let mut found = 0;
+                for point in points {
+                    index.for_each_within(*point, QUERY_RADIUS, |_, _, _| found += 1);
+                }
+                black_box(found)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("scan", count), &points, |b, points| {
+            let min_dot = QUERY_RADIUS.cos();
+            b.iter(|| {
+                let mut found = 0;
+                for point in points {
+                    found += points.iter().filter(|other| other.dot(*point) >= min_dot).count();
+                }
+                black_box(found)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn nearest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nearest");
+    for count in [1000, 5000, 20000] {
+        let points = random_points(count);
+        let index = build_index(&points);
+        let queries = random_points(256);
+
+        group.bench_with_input(BenchmarkId::new("index", count), &queries, |b, queries| {
+            b.iter(|| {
+                for query in queries {
+                    black_box(index.nearest(*query, std::f32::consts::PI, |_| true));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("scan", count), &queries, |b, queries| {
+            b.iter(|| {
+                for query in queries {
+                    black_box(
+                        points
+                            .iter()
+                            .map(|point| point.dot(*query))
+                            .max_by(|a, b| a.total_cmp(b)),
+                    );
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn cone(c: &mut Criterion) {
+    let points = random_points(5000);
+    let index = build_index(&points);
+    let queries = random_points(256);
+
+    c.bench_function("cone 5000", |b| {
+        b.iter(|| {
+            for query in &queries {
+                let facing = query.cross(Vec3::Y);
+                black_box(index.within_cone(*query, facing, 0.6, 0.3));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, rebuild, radius_all, nearest, cone);
+criterion_main!(benches);
@@ -3,6 +3,15 @@ use bevy::{
     prelude::*,
 };
 
+use crate::{planetoid::Planetoid, selection::CursorPick, state::AppState};
+
+/// How far the camera starts from the planetoid it's looking at.
+const FOCUS_DISTANCE: f32 = 5.0;
+/// How far the system view stands back, on top of the length of the system.
+const SYSTEM_VIEW_DISTANCE: f32 = 6.0;
+/// How quickly the camera catches up with a change of focus, per second.
+const FOCUS_EASE: f32 = 4.0;
+
 #[derive(Component)]
 pub(crate) struct MainCamera;
 
@@ -10,6 +19,27 @@ pub(crate) struct MainCameraTransform {
     pub(crate) value: Mat4,
 }
 
+/// What the camera is looking at: one planetoid up close, or the whole system.
+pub(crate) struct CameraFocus {
+    pub(crate) planetoid: Option<Entity>,
+    pub(crate) system_view: bool,
+    /// Point the camera circles around, easing towards whatever it's focused on.
+    pub(crate) pivot: Vec3,
+    /// How far the camera is from the focused planetoid, outside the system view.
+    pub(crate) distance: f32,
+}
+
+impl Default for CameraFocus {
+    fn default() -> Self {
+        Self {
+            planetoid: None,
+            system_view: false,
+            pivot: Vec3::ZERO,
+            distance: FOCUS_DISTANCE,
+        }
+    }
+}
+
 pub struct MainCameraPlugin;
 
 impl Plugin for MainCameraPlugin {
@@ -17,28 +47,46 @@ impl Plugin for MainCameraPlugin {
         app.insert_resource(MainCameraTransform {
             value: Mat4::IDENTITY,
         })
-        .add_system(scroll_events)
-        .add_system(camera_pan)
+        .init_resource::<CameraFocus>()
+        .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_focus))
+        .add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(scroll_events)
+                .with_system(camera_pan)
+                .with_system(focus_first_planetoid)
+                .with_system(toggle_system_view)
+                .with_system(focus_on_click)
+                .with_system(
+                    follow_focus
+                        .after(focus_first_planetoid)
+                        .after(toggle_system_view)
+                        .after(focus_on_click),
+                ),
+        )
         .add_system(update_cam_transform);
     }
 }
 
-fn scroll_events(
-    mut scroll_evr: EventReader<MouseWheel>,
-    mut query: Query<&mut Transform, With<MainCamera>>,
-) {
+fn reset_focus(mut focus: ResMut<CameraFocus>, mut query: Query<&mut Transform, With<MainCamera>>) {
+    *focus = CameraFocus::default();
     for mut transform in query.iter_mut() {
-        if let Some(mouse_wheel) = scroll_evr.iter().next() {
-            let zoom = -mouse_wheel.y;
-            let zoom_factor = 1.0 + zoom * 0.05;
+        *transform = Transform::from_translation(Vec3::new(0.0, 0.0, -FOCUS_DISTANCE))
+            .looking_at(Vec3::ZERO, Vec3::Y);
+    }
+}
 
-            transform.translation *= zoom_factor;
-        }
+fn scroll_events(mut scroll_evr: EventReader<MouseWheel>, mut focus: ResMut<CameraFocus>) {
+    if let Some(mouse_wheel) = scroll_evr.iter().next() {
+        let zoom = -mouse_wheel.y;
+        let zoom_factor = 1.0 + zoom * 0.05;
+
+        focus.distance *= zoom_factor;
     }
 }
 
 fn camera_pan(
     buttons: Res<Input<MouseButton>>,
+    focus: Res<CameraFocus>,
     mut mouse_motion_evr: EventReader<MouseMotion>,
     mut query: Query<&mut Transform, With<MainCamera>>,
 ) {
@@ -55,16 +103,103 @@ fn camera_pan(
 
             let delta = Mat4::from_quat(rot_y * rot_x);
 
+            // orbit around the pivot rather than the origin
+            transform.translation -= focus.pivot;
             *transform = Transform::from_matrix(delta).mul_transform(*transform);
+            transform.translation += focus.pivot;
         }
     }
 }
 
+/// Looks at the first planetoid whenever the camera isn't looking at any, like in a new
+/// world.
+fn focus_first_planetoid(mut focus: ResMut<CameraFocus>, planetoids: Query<(Entity, &Planetoid)>) {
+    if focus
+        .planetoid
+        .map_or(false, |planetoid| planetoids.get(planetoid).is_ok())
+    {
+        return;
+    }
+    focus.planetoid = planetoids
+        .iter()
+        .find(|(_, planetoid)| planetoid.index == 0)
+        .map(|(entity, _)| entity);
+}
+
+fn toggle_system_view(keys: Res<Input<KeyCode>>, mut focus: ResMut<CameraFocus>) {
+    if keys.just_pressed(KeyCode::V) {
+        focus.system_view = !focus.system_view;
+    }
+}
+
+/// Clicking a planetoid in the system view flies over to it.
+fn focus_on_click(
+    buttons: Res<Input<MouseButton>>,
+    pick: Res<CursorPick>,
+    mut focus: ResMut<CameraFocus>,
+) {
+    if !focus.system_view || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if let Some(planetoid) = pick.planetoid {
+        focus.planetoid = Some(planetoid);
+        focus.system_view = false;
+    }
+}
+
+fn follow_focus(
+    time: Res<Time>,
+    mut focus: ResMut<CameraFocus>,
+    planetoids: Query<(Entity, &Transform), (With<Planetoid>, Without<MainCamera>)>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    let positions: Vec<(Entity, Vec3)> = planetoids
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation))
+        .collect();
+    if positions.is_empty() {
+        return;
+    }
+
+    let (target, distance) = if focus.system_view {
+        let center = positions
+            .iter()
+            .map(|(_, position)| *position)
+            .sum::<Vec3>()
+            / positions.len() as f32;
+        let length = positions
+            .iter()
+            .map(|(_, position)| position.distance(center) * 2.0)
+            .fold(0.0, f32::max);
+        (center, SYSTEM_VIEW_DISTANCE + length)
+    } else {
+        let target = positions
+            .iter()
+            .find(|(entity, _)| Some(*entity) == focus.planetoid)
+            .map_or(focus.pivot, |(_, position)| *position);
+        (target, focus.distance)
+    };
+
+    let ease = (FOCUS_EASE * time.delta_seconds()).min(1.0);
+    let pivot = focus.pivot.lerp(target, ease);
+    for mut transform in cameras.iter_mut() {
+        let offset = transform.translation - focus.pivot;
+        let length = offset.length();
+        transform.translation =
+            pivot + offset.normalize_or_zero() * (length + (distance - length) * ease);
+    }
+    focus.pivot = pivot;
+}
+
 fn update_cam_transform(
     mut cam_transform: ResMut<MainCameraTransform>,
     query: Query<&mut Transform, With<MainCamera>>,
 ) {
     if let Some(transform) = query.iter().next() {
-        cam_transform.value = transform.compute_matrix();
+        // only touched when it moves, so the post-process materials know when to update
+        let value = transform.compute_matrix();
+        if cam_transform.value != value {
+            cam_transform.value = value;
+        }
     }
 }
@@ -0,0 +1,83 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::state::AppState;
+
+/// Radians per second the sun travels along its orbit.
+pub(crate) const SUN_ANGULAR_SPEED: f32 = 0.25;
+/// One day is one full orbit of the sun.
+pub(crate) const DAY_LENGTH: f32 = 2.0 * PI / SUN_ANGULAR_SPEED;
+/// Days each season lasts.
+pub(crate) const SEASON_LENGTH: u32 = 4;
+
+/// Rotation of the plane the sun, and the moons with it, orbit in.
+pub(crate) fn ecliptic() -> Quat {
+    Quat::from_rotation_z(PI / 4.0)
+}
+
+pub(crate) struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameClock::default())
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_clock))
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(advance_clock));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+/// In-game time, which only advances while the world is simulated.
+#[derive(Default)]
+pub(crate) struct GameClock {
+    pub(crate) elapsed: f32,
+}
+
+impl GameClock {
+    pub(crate) fn day(&self) -> u32 {
+        (self.elapsed / DAY_LENGTH) as u32
+    }
+
+    /// Fraction of the current day that has passed, in `0.0..1.0`.
+    pub(crate) fn time_of_day(&self) -> f32 {
+        (self.elapsed / DAY_LENGTH).fract()
+    }
+
+    pub(crate) fn hour(&self) -> u32 {
+        (self.time_of_day() * 24.0) as u32
+    }
+
+    pub(crate) fn season(&self) -> Season {
+        match (self.day() / SEASON_LENGTH) % 4 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    pub(crate) fn sun_angle(&self) -> f32 {
+        self.elapsed * SUN_ANGULAR_SPEED
+    }
+
+    /// Direction from the center of the planetoid towards the sun.
+    pub(crate) fn sun_direction(&self) -> Vec3 {
+        let orbit = ecliptic() * Quat::from_rotation_y(self.sun_angle());
+        orbit * Vec3::X
+    }
+}
+
+fn reset_clock(mut clock: ResMut<GameClock>) {
+    *clock = GameClock::default();
+}
+
+fn advance_clock(time: Res<Time>, mut clock: ResMut<GameClock>) {
+    clock.elapsed += time.delta_seconds();
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+use crate::planetoid::transform::{
+    normalized_sphere_to_cartesian, tangent_towards, PlanetoidTransform, SurfaceIndex,
+};
+
+use super::{
+    species::{SpeciesCatalog, SpeciesId, SpeciesLibrary},
+    Creature,
+};
+
+/// Whether species with flocking weights move as herds.
+pub(crate) struct FlockingSettings {
+    pub(crate) enabled: bool,
+}
+
+impl Default for FlockingSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Direction a creature last moved in, as a unit tangent at its position on the unit
+/// sphere. Zero while standing still.
+#[derive(Component, Default)]
+pub(crate) struct Heading(pub(crate) Vec3);
+
+/// Push from nearby herd mates, as a tangent at the creature's position on the unit sphere.
+/// Added to the pull towards the creature's goal when it moves.
+#[derive(Component, Default)]
+pub(crate) struct Steering(pub(crate) Vec3);
+
+pub(crate) fn toggle_flocking(keys: Res<Input<KeyCode>>, mut settings: ResMut<FlockingSettings>) {
+    if keys.just_pressed(KeyCode::H) {
+        settings.enabled = !settings.enabled;
+        bevy::log::info!("flocking enabled: {}", settings.enabled);
+    }
+}
+
+/// Separation, alignment and cohesion between herd mates of the same species, worked out
+/// in the tangent plane of each creature's position.
+pub(crate) fn flock(
+    settings: Res<FlockingSettings>,
+    index: Res<SurfaceIndex>,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    mut creatures: Query<
+        (
+            Entity,
+            &SpeciesId,
+            &PlanetoidTransform,
+            &Parent,
+            &mut Steering,
+        ),
+        With<Creature>,
+    >,
+    mates: Query<(&SpeciesId, &PlanetoidTransform, &Heading), With<Creature>>,
+) {
+    for (entity, species_id, transform, parent, mut steering) in creatures.iter_mut() {
+        let flocking = match library.get(&catalogs, &species_id.0) {
+            Some(species) if settings.enabled => species.flocking.as_ref(),
+            _ => None,
+        };
+        let flocking = match flocking {
+            Some(flocking) => flocking,
+            None => {
+                steering.0 = Vec3::ZERO;
+                continue;
+            }
+        };
+
+        let position = normalized_sphere_to_cartesian(transform.sphere_coords);
+        let mut separation = Vec3::ZERO;
+        let mut alignment = Vec3::ZERO;
+        let mut center = Vec3::ZERO;
+        let mut count = 0;
+
+        let herd = index.within(parent.get(), transform.sphere_coords, flocking.radius);
+        for (mate, distance) in herd {
+            if mate == entity {
+                continue;
+            }
+            let (mate_transform, mate_heading) = match mates.get(mate) {
+                Ok((mate_species, mate_transform, mate_heading))
+                    if mate_species.0 == species_id.0 =>
+                {
+                    (mate_transform, mate_heading)
+                }
+                _ => continue,
+            };
+
+            let mate_position = normalized_sphere_to_cartesian(mate_transform.sphere_coords);
+            // stronger the closer they are, fading out at the edge of the herd radius
+            separation -=
+                tangent_towards(position, mate_position) * (1.0 - distance / flocking.radius);
+            // headings are tangents at the mate's position, so flatten them onto ours
+            alignment += mate_heading.0 - position * mate_heading.0.dot(position);
+            center += mate_position;
+            count += 1;
+        }
+
+        if count == 0 {
+            steering.0 = Vec3::ZERO;
+            continue;
+        }
+
+        let cohesion = tangent_towards(position, center.normalize_or_zero());
+        steering.0 = separation * flocking.separation
+            + alignment / count as f32 * flocking.alignment
+            + cohesion * flocking.cohesion;
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use super::species::Species;
+
+/// Chance for each gene to mutate when a creature is born.
+const MUTATION_CHANCE: f64 = 0.2;
+/// Largest change a single mutation makes to a gene.
+const MUTATION_SIZE: f32 = 0.15;
+
+/// Heritable traits, passed on to offspring with crossover and mutation.
+#[derive(Component, Clone, Debug)]
+pub(crate) struct Genome {
+    pub(crate) color: Vec3,
+    /// Multiplier on the size of the mesh.
+    pub(crate) size: f32,
+    /// Multiplier on the species' speed.
+    pub(crate) speed: f32,
+    /// Temperature the creature is comfortable at, 0.0 polar to 1.0 equatorial.
+    pub(crate) temperature_tolerance: f32,
+    /// 0.0 eats only plants, 1.0 only meat.
+    pub(crate) diet_preference: f32,
+}
+
+impl Genome {
+    /// Genome for a creature without parents, close to the species' defaults.
+    pub(crate) fn founder(species: &Species, variant: usize, rng: &mut impl Rng) -> Self {
+        let [r, g, b, _] = species.color(variant).as_rgba_f32();
+        let mut genome = Self {
+            color: Vec3::new(r, g, b),
+            size: 1.0,
+            speed: 1.0,
+            temperature_tolerance: species.comfort_temperature(),
+            diet_preference: species.diet.meat_preference(),
+        };
+        genome.mutate(rng);
+        genome
+    }
+
+    /// Each gene comes from one of the parents, except color, which blends.
+    pub(crate) fn crossover(a: &Genome, b: &Genome, rng: &mut impl Rng) -> Self {
+        let mut pick = |a: f32, b: f32| if rng.gen_bool(0.5) { a } else { b };
+        let size = pick(a.size, b.size);
+        let speed = pick(a.speed, b.speed);
+        let temperature_tolerance = pick(a.temperature_tolerance, b.temperature_tolerance);
+        let diet_preference = pick(a.diet_preference, b.diet_preference);
+
+        Self {
+            color: a.color.lerp(b.color, rng.gen_range(0.0..=1.0)),
+            size,
+            speed,
+            temperature_tolerance,
+            diet_preference,
+        }
+    }
+
+    pub(crate) fn mutate(&mut self, rng: &mut impl Rng) {
+        let mut drift = |value: &mut f32, min: f32, max: f32| {
+            if rng.gen_bool(MUTATION_CHANCE) {
+                *value = (*value + rng.gen_range(-MUTATION_SIZE..=MUTATION_SIZE)).clamp(min, max);
+            }
+        };
+
+        drift(&mut self.color.x, 0.0, 1.0);
+        drift(&mut self.color.y, 0.0, 1.0);
+        drift(&mut self.color.z, 0.0, 1.0);
+        drift(&mut self.size, 0.5, 1.6);
+        drift(&mut self.speed, 0.5, 1.8);
+        drift(&mut self.temperature_tolerance, 0.0, 1.0);
+        drift(&mut self.diet_preference, 0.0, 1.0);
+    }
+
+    pub(crate) fn body_color(&self) -> Color {
+        Color::rgb(self.color.x, self.color.y, self.color.z)
+    }
+}
+
+/// Stable identity of a creature, which unlike its entity survives its death.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct CreatureId(pub(crate) u32);
+
+#[derive(Clone, Debug)]
+pub(crate) struct LineageRecord {
+    pub(crate) species: String,
+    pub(crate) generation: u32,
+    pub(crate) parents: Option<(CreatureId, CreatureId)>,
+}
+
+/// Family tree of every creature that lived in the current world.
+#[derive(Default)]
+pub(crate) struct LineageBook {
+    next_id: u32,
+    records: HashMap<CreatureId, LineageRecord>,
+}
+
+impl LineageBook {
+    pub(crate) fn register(
+        &mut self,
+        species: &str,
+        parents: Option<(CreatureId, CreatureId)>,
+    ) -> CreatureId {
+        let id = CreatureId(self.next_id);
+        self.next_id += 1;
+
+        let generation = parents
+            .map(|(a, b)| {
+                let generation = |id| self.get(id).map_or(0, |record| record.generation);
+                generation(a).max(generation(b)) + 1
+            })
+            .unwrap_or(0);
+
+        self.records.insert(
+            id,
+            LineageRecord {
+                species: species.to_string(),
+                generation,
+                parents,
+            },
+        );
+        id
+    }
+
+    pub(crate) fn get(&self, id: CreatureId) -> Option<&LineageRecord> {
+        self.records.get(&id)
+    }
+}
+
+pub(crate) fn reset_lineage(mut book: ResMut<LineageBook>) {
+    *book = LineageBook::default();
+}
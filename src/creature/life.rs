@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    clock::DAY_LENGTH,
+    planetoid::transform::{move_towards, PlanetoidTransform, SurfaceIndex},
+    state::WorldRng,
+};
+
+use super::{
+    genetics::Genome,
+    species::{SpeciesCatalog, SpeciesId, SpeciesLibrary},
+    Creature, Needs, SpawnCreature,
+};
+
+/// Creatures closer than this many radians can mate.
+const MATE_RADIUS: f32 = 0.15;
+/// Chance per day that an eligible pair actually mates.
+const MATE_CHANCE_PER_DAY: f32 = 2.0;
+/// Days a creature has to wait after mating before it can mate again.
+const MATE_COOLDOWN: f32 = 1.5;
+/// Creatures need to be at least this well fed to mate.
+const MATE_MIN_HUNGER: f32 = 0.4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum LifeStage {
+    Baby,
+    Juvenile,
+    Adult,
+    Elder,
+}
+
+impl LifeStage {
+    /// Stage at the given fraction of the species' lifespan.
+    pub(crate) fn from_maturity(maturity: f32) -> Self {
+        if maturity < 0.1 {
+            LifeStage::Baby
+        } else if maturity < 0.25 {
+            LifeStage::Juvenile
+        } else if maturity < 0.8 {
+            LifeStage::Adult
+        } else {
+            LifeStage::Elder
+        }
+    }
+
+    pub(crate) fn scale(self) -> f32 {
+        match self {
+            LifeStage::Baby => 0.4,
+            LifeStage::Juvenile => 0.7,
+            LifeStage::Adult => 1.0,
+            LifeStage::Elder => 0.9,
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct Age {
+    pub(crate) days: f32,
+    pub(crate) stage: LifeStage,
+}
+
+#[derive(Component, Default)]
+pub(crate) struct Fertility {
+    /// Days until the creature can mate again.
+    pub(crate) cooldown: f32,
+}
+
+/// Upper bound on living creatures, above which no more offspring are conceived.
+pub(crate) struct PopulationCap(pub(crate) usize);
+
+impl Default for PopulationCap {
+    fn default() -> Self {
+        Self(32)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum DeathCause {
+    OldAge,
+    Starvation,
+    Thirst,
+    Predation,
+}
+
+pub(crate) struct CreatureBorn {
+    pub(crate) entity: Entity,
+    pub(crate) species: String,
+    pub(crate) parents: Option<(Entity, Entity)>,
+}
+
+pub(crate) struct CreatureGrew {
+    pub(crate) entity: Entity,
+    pub(crate) stage: LifeStage,
+}
+
+pub(crate) struct CreatureMated {
+    pub(crate) parents: (Entity, Entity),
+}
+
+pub(crate) struct CreatureDied {
+    pub(crate) entity: Entity,
+    pub(crate) species: String,
+    /// Where the creature died, and on which planetoid.
+    pub(crate) planetoid: Entity,
+    pub(crate) sphere_coords: Vec2,
+    pub(crate) cause: DeathCause,
+}
+
+pub(crate) fn age_creatures(
+    time: Res<Time>,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    mut grew_events: EventWriter<CreatureGrew>,
+    mut query: Query<
+        (Entity, &SpeciesId, &Genome, &mut Age, &mut PlanetoidTransform),
+        With<Creature>,
+    >,
+) {
+    let days = time.delta_seconds() / DAY_LENGTH;
+    for (entity, species_id, genome, mut age, mut transform) in query.iter_mut() {
+        let species = match library.get(&catalogs, &species_id.0) {
+            Some(species) => species,
+            None => continue,
+        };
+
+        age.days += days;
+
+        let stage = LifeStage::from_maturity(age.days / species.lifespan);
+        if stage != age.stage {
+            age.stage = stage;
+            grew_events.send(CreatureGrew { entity, stage });
+        }
+        transform.scale = stage.scale() * genome.size;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn mate_creatures(
+    time: Res<Time>,
+    cap: Res<PopulationCap>,
+    index: Res<SurfaceIndex>,
+    mut rng: ResMut<WorldRng>,
+    mut spawn_events: EventWriter<SpawnCreature>,
+    mut mated_events: EventWriter<CreatureMated>,
+    mut query: Query<
+        (
+            Entity,
+            &SpeciesId,
+            &Age,
+            &Needs,
+            &PlanetoidTransform,
+            &Parent,
+            &mut Fertility,
+        ),
+        With<Creature>,
+    >,
+) {
+    let days = time.delta_seconds() / DAY_LENGTH;
+    let mut population = query.iter().count();
+
+    for (_, _, _, _, _, _, mut fertility) in query.iter_mut() {
+        fertility.cooldown = (fertility.cooldown - days).max(0.0);
+    }
+
+    let eligible: Vec<(Entity, String, Entity, Vec2)> = query
+        .iter()
+        .filter(|(_, _, age, needs, _, _, fertility)| {
+            age.stage == LifeStage::Adult
+                && fertility.cooldown <= 0.0
+                && needs.hunger >= MATE_MIN_HUNGER
+        })
+        .map(|(entity, species, _, _, transform, parent, _)| {
+            (
+                entity,
+                species.0.clone(),
+                parent.get(),
+                transform.sphere_coords,
+            )
+        })
+        .collect();
+
+    let mut mated = Vec::new();
+    for (a, species_a, planetoid, pos_a) in eligible.iter() {
+        if population >= cap.0 {
+            break;
+        }
+        if mated.contains(a) {
+            continue;
+        }
+
+        for (b, distance) in index.within(*planetoid, *pos_a, MATE_RADIUS) {
+            let pos_b = match eligible.iter().find(|(entity, _, _, _)| *entity == b) {
+                Some((_, species_b, _, pos_b)) if species_b == species_a => *pos_b,
+                _ => continue,
+            };
+            let chance = (MATE_CHANCE_PER_DAY * days).min(1.0) as f64;
+            if b == *a || mated.contains(&b) || !rng.0.gen_bool(chance) {
+                continue;
+            }
+
+            mated.push(*a);
+            mated.push(b);
+            population += 1;
+
+            spawn_events.send(SpawnCreature {
+                species: species_a.clone(),
+                planetoid: *planetoid,
+                sphere_coords: move_towards(*pos_a, pos_b, distance / 2.0),
+                maturity: 0.0,
+                parents: Some((*a, b)),
+            });
+            mated_events.send(CreatureMated { parents: (*a, b) });
+            break;
+        }
+    }
+
+    for entity in mated {
+        if let Ok((_, _, _, _, _, _, mut fertility)) = query.get_mut(entity) {
+            fertility.cooldown = MATE_COOLDOWN;
+        }
+    }
+}
+
+pub(crate) fn natural_death(
+    mut commands: Commands,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    mut died_events: EventWriter<CreatureDied>,
+    query: Query<
+        (
+            Entity,
+            &SpeciesId,
+            &Age,
+            &Needs,
+            &PlanetoidTransform,
+            &Parent,
+        ),
+        With<Creature>,
+    >,
+) {
+    for (entity, species_id, age, needs, transform, parent) in query.iter() {
+        let lifespan = match library.get(&catalogs, &species_id.0) {
+            Some(species) => species.lifespan,
+            None => continue,
+        };
+
+        let cause = if age.days >= lifespan {
+            DeathCause::OldAge
+        } else if needs.hunger <= 0.0 {
+            DeathCause::Starvation
+        } else if needs.thirst <= 0.0 {
+            DeathCause::Thirst
+        } else {
+            continue;
+        };
+
+        commands.entity(entity).despawn_recursive();
+        died_events.send(CreatureDied {
+            entity,
+            species: species_id.0.clone(),
+            planetoid: parent.get(),
+            sphere_coords: transform.sphere_coords,
+            cause,
+        });
+    }
+}
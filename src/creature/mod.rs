@@ -0,0 +1,404 @@
+use bevy::prelude::*;
+
+use crate::{
+    clock::DAY_LENGTH,
+    planetoid::{
+        terrain::{biome_at, temperature},
+        transform::{
+            great_circle_distance, move_along, move_towards, normalized_sphere_to_cartesian,
+            tangent_towards, PlanetoidTransform,
+        },
+        Planetoid,
+    },
+    state::{AppState, InGame, WorldRng},
+    GameWorldRenderLayer,
+};
+
+use self::flocking::{flock, toggle_flocking, FlockingSettings, Heading, Steering};
+use self::genetics::{reset_lineage, CreatureId, Genome, LineageBook};
+use self::life::{
+    age_creatures, mate_creatures, natural_death, Age, CreatureBorn, CreatureDied, CreatureGrew,
+    CreatureMated, Fertility, LifeStage, PopulationCap,
+};
+use self::model::{
+    paint_bodies, play_animations, update_animation_states, Body, CreatureAnimation,
+};
+use self::species::{
+    setup_species_library, SpeciesCatalog, SpeciesId, SpeciesLibrary, SpeciesLoader,
+};
+
+pub(crate) mod flocking;
+pub(crate) mod genetics;
+pub(crate) mod life;
+pub(crate) mod model;
+pub(crate) mod species;
+
+/// Starting creatures are spawned this far into their lifespan, as young adults.
+const STARTING_MATURITY: f32 = 0.3;
+
+/// Species and positions of the creatures every new world starts with.
+const STARTING_CREATURES: [(&str, Vec2); 4] = [
+    ("bloop", Vec2::new(0.0, 0.0)),
+    ("ember", Vec2::new(0.5, 0.0)),
+    ("sprig", Vec2::new(0.0, 0.5)),
+    ("mauve", Vec2::new(0.5, 0.5)),
+];
+
+/// Creatures closer than this many radians to their goal have arrived.
+const ARRIVAL_RADIUS: f32 = 0.02;
+/// Herds have arrived once within this fraction of their flocking radius of the goal, as
+/// they can't all stand on the same spot.
+const HERD_ARRIVAL_FRACTION: f32 = 0.5;
+/// Weaker pushes than this leave a creature without a goal standing still, so herds settle.
+const MIN_STEERING: f32 = 0.1;
+
+/// Energy is lost this much faster outside the species' preferred biome.
+const FOREIGN_BIOME_ENERGY_FACTOR: f32 = 1.5;
+/// Energy is lost this much faster per unit of temperature outside the creature's tolerance.
+const DISCOMFORT_ENERGY_FACTOR: f32 = 2.0;
+/// How far the local temperature may stray from a creature's tolerance before it suffers.
+const TEMPERATURE_COMFORT_RANGE: f32 = 0.25;
+
+pub(crate) struct CreaturePlugin;
+
+impl Plugin for CreaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<SpeciesCatalog>()
+            .init_asset_loader::<SpeciesLoader>()
+            .init_resource::<PopulationCap>()
+            .init_resource::<LineageBook>()
+            .init_resource::<FlockingSettings>()
+            .add_event::<SpawnCreature>()
+            .add_event::<CreatureBorn>()
+            .add_event::<CreatureGrew>()
+            .add_event::<CreatureMated>()
+            .add_event::<CreatureDied>()
+            .add_startup_system(setup_species_library)
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_lineage))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(setup_creatures)
+                    .with_system(spawn_creatures.after(setup_creatures))
+                    .with_system(toggle_flocking)
+                    .with_system(flock)
+                    .with_system(creature_movement)
+                    .with_system(paint_bodies)
+                    .with_system(update_animation_states.after(creature_movement))
+                    .with_system(play_animations.after(update_animation_states))
+                    .with_system(decay_needs)
+                    .with_system(age_creatures)
+                    .with_system(mate_creatures)
+                    .with_system(natural_death),
+            )
+            .add_system(reload_species);
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct Creature;
+
+/// How satisfied each of a creature's needs is, from 0.0 (desperate) to 1.0 (content).
+#[derive(Component)]
+pub(crate) struct Needs {
+    pub(crate) hunger: f32,
+    pub(crate) thirst: f32,
+    pub(crate) energy: f32,
+}
+
+impl Default for Needs {
+    fn default() -> Self {
+        Self {
+            hunger: 1.0,
+            thirst: 1.0,
+            energy: 1.0,
+        }
+    }
+}
+
+/// Where a creature is heading. Goals the player commanded take precedence over the ones
+/// creatures pick for themselves, until the creature gets there.
+#[derive(Component, Default)]
+pub(crate) struct Goal {
+    pub(crate) target: Option<Vec2>,
+    pub(crate) commanded: bool,
+}
+
+impl Goal {
+    pub(crate) fn command(&mut self, target: Vec2) {
+        self.target = Some(target);
+        self.commanded = true;
+    }
+}
+
+/// Spawns a creature of the given species once the species catalogs are loaded.
+pub(crate) struct SpawnCreature {
+    pub(crate) species: String,
+    pub(crate) planetoid: Entity,
+    pub(crate) sphere_coords: Vec2,
+    /// Fraction of the species' lifespan the creature has already lived.
+    pub(crate) maturity: f32,
+    pub(crate) parents: Option<(Entity, Entity)>,
+}
+
+/// Every planetoid starts out with the same few creatures.
+fn setup_creatures(
+    mut spawn_events: EventWriter<SpawnCreature>,
+    planetoids: Query<Entity, Added<Planetoid>>,
+) {
+    for planetoid in planetoids.iter() {
+        for (species, sphere_coords) in STARTING_CREATURES {
+            spawn_events.send(SpawnCreature {
+                species: species.to_string(),
+                planetoid,
+                sphere_coords,
+                maturity: STARTING_MATURITY,
+                parents: None,
+            });
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_creatures(
+    mut commands: Commands,
+    mut spawn_events: EventReader<SpawnCreature>,
+    mut born_events: EventWriter<CreatureBorn>,
+    mut pending: Local<Vec<SpawnCreature>>,
+    mut spawned: Local<usize>,
+    mut rng: ResMut<WorldRng>,
+    mut lineage: ResMut<LineageBook>,
+    parents: Query<(&Genome, &CreatureId)>,
+    planetoids: Query<(), With<Planetoid>>,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    pending.extend(spawn_events.iter().map(|event| SpawnCreature {
+        species: event.species.clone(),
+        planetoid: event.planetoid,
+        sphere_coords: event.sphere_coords,
+        maturity: event.maturity,
+        parents: event.parents,
+    }));
+
+    if pending.is_empty() || !library.is_loaded(&catalogs) {
+        return;
+    }
+
+    for event in pending.drain(..) {
+        // the world may have been left while the catalogs were loading
+        if planetoids.get(event.planetoid).is_err() {
+            continue;
+        }
+        let species = match library.get(&catalogs, &event.species) {
+            Some(species) => species,
+            None => {
+                bevy::log::warn!("cannot spawn creature of unknown species {}", event.species);
+                continue;
+            }
+        };
+
+        let variant = *spawned;
+        *spawned += 1;
+
+        let age_days = event.maturity * species.lifespan;
+        let stage = LifeStage::from_maturity(event.maturity);
+
+        let parent_genomes = event
+            .parents
+            .and_then(|(a, b)| Some((parents.get(a).ok()?, parents.get(b).ok()?)));
+        let (genome, parent_ids) = match parent_genomes {
+            Some(((genome_a, id_a), (genome_b, id_b))) => {
+                let mut genome = Genome::crossover(genome_a, genome_b, &mut rng.0);
+                genome.mutate(&mut rng.0);
+                (genome, Some((*id_a, *id_b)))
+            }
+            None => (Genome::founder(species, variant, &mut rng.0), None),
+        };
+        let id = lineage.register(&event.species, parent_ids);
+
+        let entity = commands
+            .spawn_bundle(SceneBundle {
+                scene: asset_server.load(&species.scene),
+                ..default()
+            })
+            .insert(Body(materials.add(StandardMaterial {
+                base_color: genome.body_color(),
+                unlit: true,
+                ..default()
+            })))
+            .insert(CreatureAnimation::default())
+            .insert(PlanetoidTransform {
+                sphere_coords: event.sphere_coords,
+                rotation: 0.0,
+                scale: stage.scale() * genome.size,
+            })
+            .insert(Creature)
+            .insert(SpeciesId(event.species.clone()))
+            .insert(Age {
+                days: age_days,
+                stage,
+            })
+            .insert(Fertility::default())
+            .insert(genome)
+            .insert(id)
+            .insert(Needs::default())
+            .insert(Goal::default())
+            .insert(Heading::default())
+            .insert(Steering::default())
+            .insert(InGame)
+            .insert(game_world_render_layer.0)
+            .id();
+        commands.entity(event.planetoid).add_child(entity);
+
+        born_events.send(CreatureBorn {
+            entity,
+            species: event.species,
+            parents: event.parents,
+        });
+    }
+}
+
+/// Applies edits to species catalogs to creatures that are already alive.
+fn reload_species(
+    mut asset_events: EventReader<AssetEvent<SpeciesCatalog>>,
+    library: Option<Res<SpeciesLibrary>>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    asset_server: Res<AssetServer>,
+    mut query: Query<(&SpeciesId, &mut Handle<Scene>)>,
+) {
+    let library = match library {
+        Some(library) => library,
+        None => return,
+    };
+
+    let modified = asset_events.iter().any(|event| match event {
+        AssetEvent::Modified { handle } => library.contains(handle),
+        _ => false,
+    });
+    if !modified {
+        return;
+    }
+
+    bevy::log::info!("species catalog changed, updating creatures");
+    // colors are inherited, so palette changes only show up in newly founded lineages
+    for (species_id, mut scene) in query.iter_mut() {
+        if let Some(species) = library.get(&catalogs, &species_id.0) {
+            // a new handle respawns the scene, so leave unchanged ones alone
+            let reloaded = asset_server.load(&species.scene);
+            if *scene != reloaded {
+                *scene = reloaded;
+            }
+        }
+    }
+}
+
+fn creature_movement(
+    time: Res<Time>,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    mut query: Query<
+        (
+            &mut PlanetoidTransform,
+            &mut Goal,
+            &mut Heading,
+            &Steering,
+            &SpeciesId,
+            &Genome,
+        ),
+        With<Creature>,
+    >,
+) {
+    for (mut transform, mut goal, mut heading, steering, species_id, genome) in query.iter_mut() {
+        let species = match library.get(&catalogs, &species_id.0) {
+            Some(species) => species,
+            None => continue,
+        };
+
+        let position = normalized_sphere_to_cartesian(transform.sphere_coords);
+        let mut direction = steering.0;
+        if let Some(target) = goal.target {
+            direction += tangent_towards(position, normalized_sphere_to_cartesian(target));
+        } else if direction.length() < MIN_STEERING {
+            heading.0 = Vec3::ZERO;
+            continue;
+        }
+        if direction.length() > 1.0 {
+            direction = direction.normalize();
+        }
+
+        let step = species.speed * genome.speed * time.delta_seconds();
+        match goal.target {
+            // walking straight at the goal, so don't overshoot it
+            Some(target) if steering.0 == Vec3::ZERO => {
+                transform.sphere_coords = move_towards(transform.sphere_coords, target, step);
+            }
+            _ => {
+                transform.sphere_coords =
+                    move_along(transform.sphere_coords, direction, step * direction.length());
+            }
+        }
+        heading.0 = direction.normalize_or_zero();
+
+        let arrival_radius = match &species.flocking {
+            Some(flocking) if steering.0 != Vec3::ZERO => {
+                ARRIVAL_RADIUS.max(flocking.radius * HERD_ARRIVAL_FRACTION)
+            }
+            _ => ARRIVAL_RADIUS,
+        };
+        let arrived = goal.target.map_or(false, |target| {
+            great_circle_distance(transform.sphere_coords, target) < arrival_radius
+        });
+        if arrived {
+            *goal = Goal::default();
+            heading.0 = Vec3::ZERO;
+        }
+    }
+}
+
+fn decay_needs(
+    time: Res<Time>,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    planetoids: Query<&Planetoid>,
+    mut query: Query<
+        (
+            &mut Needs,
+            &SpeciesId,
+            &Genome,
+            &PlanetoidTransform,
+            &Parent,
+        ),
+        With<Creature>,
+    >,
+) {
+    let days = time.delta_seconds() / DAY_LENGTH;
+    for (mut needs, species_id, genome, transform, parent) in query.iter_mut() {
+        let (species, params) = match (
+            library.get(&catalogs, &species_id.0),
+            planetoids.get(parent.get()),
+        ) {
+            (Some(species), Ok(planetoid)) => (species, &planetoid.params),
+            _ => continue,
+        };
+
+        let mut energy_factor =
+            if biome_at(transform.sphere_coords, params) == species.preferred_biome {
+                1.0
+            } else {
+                FOREIGN_BIOME_ENERGY_FACTOR
+            };
+
+        let discomfort =
+            ((temperature(transform.sphere_coords, params) - genome.temperature_tolerance).abs()
+                - TEMPERATURE_COMFORT_RANGE)
+                .max(0.0);
+        energy_factor += discomfort * DISCOMFORT_ENERGY_FACTOR;
+
+        needs.hunger = (needs.hunger - species.needs.hunger * days).max(0.0);
+        needs.thirst = (needs.thirst - species.needs.thirst * days).max(0.0);
+        needs.energy = (needs.energy - species.needs.energy * energy_factor * days).max(0.0);
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::{prelude::*, render::view::RenderLayers};
+
+use crate::{
+    clock::GameClock,
+    planetoid::{in_daylight, transform::PlanetoidTransform, Planetoid},
+    GameWorldRenderLayer,
+};
+
+use super::{
+    flocking::Heading,
+    species::{SpeciesCatalog, SpeciesId, SpeciesLibrary},
+    Creature, Needs,
+};
+
+/// Creatures this tired fall asleep wherever they stand.
+const SLEEPY_BELOW: f32 = 0.25;
+
+/// Flat color every mesh of an entity's glTF scene is drawn in, in place of the materials
+/// the scene comes with.
+#[derive(Component)]
+pub(crate) struct Body(pub(crate) Handle<StandardMaterial>);
+
+/// What a creature is seen doing, each with its own animation clip. Morph targets aren't
+/// supported by the renderer, so the clips can only move the skeleton.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum AnimationState {
+    #[default]
+    Idle,
+    Walk,
+    Eat,
+    Sleep,
+}
+
+#[derive(Component, Default)]
+pub(crate) struct CreatureAnimation {
+    pub(crate) state: AnimationState,
+    /// Seconds spent in the current state.
+    elapsed: f32,
+    /// Frame of the clip last shown, if the clip is playing at all.
+    frame: Option<u32>,
+    /// The animation player somewhere in the creature's scene, once it's spawned.
+    player: Option<Entity>,
+    /// Needs as of the last frame, to tell eating and resting apart from standing around.
+    last_needs: Option<(f32, f32, f32)>,
+}
+
+/// Paints the meshes of newly spawned scenes with the body material of the entity they
+/// belong to, and puts them on the game world layer. Scene meshes come without render
+/// layers, which keeps anything spawned by the game itself out of this.
+pub(crate) fn paint_bodies(
+    mut commands: Commands,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    bodies: Query<&Body>,
+    parents: Query<&Parent>,
+    mut meshes: Query<
+        (Entity, &mut Handle<StandardMaterial>),
+        (Added<Handle<StandardMaterial>>, Without<RenderLayers>),
+    >,
+) {
+    for (entity, mut material) in meshes.iter_mut() {
+        let mut ancestor = entity;
+        while let Ok(parent) = parents.get(ancestor) {
+            ancestor = parent.get();
+            if let Ok(body) = bodies.get(ancestor) {
+                *material = body.0.clone();
+                commands.entity(entity).insert(game_world_render_layer.0);
+                break;
+            }
+        }
+    }
+}
+
+/// Works out what each creature is doing: walking while it moves, eating or drinking while
+/// its hunger or thirst goes up, and sleeping when it's tired, in a shelter or standing
+/// still on the night side.
+pub(crate) fn update_animation_states(
+    clock: Res<GameClock>,
+    planetoids: Query<&Transform, With<Planetoid>>,
+    mut creatures: Query<
+        (
+            &Heading,
+            &Needs,
+            &PlanetoidTransform,
+            &Parent,
+            &mut CreatureAnimation,
+        ),
+        With<Creature>,
+    >,
+) {
+    let sun = clock.sun_direction();
+    for (heading, needs, transform, parent, mut animation) in creatures.iter_mut() {
+        let current = (needs.hunger, needs.thirst, needs.energy);
+        let (hunger, thirst, energy) = animation.last_needs.unwrap_or(current);
+        animation.last_needs = Some(current);
+
+        let night = planetoids.get(parent.get()).map_or(false, |planetoid| {
+            !in_daylight(planetoid, transform.sphere_coords, sun)
+        });
+        let state = if heading.0 != Vec3::ZERO {
+            AnimationState::Walk
+        } else if needs.hunger > hunger || needs.thirst > thirst {
+            AnimationState::Eat
+        } else if needs.energy > energy || needs.energy < SLEEPY_BELOW || night {
+            AnimationState::Sleep
+        } else {
+            AnimationState::Idle
+        };
+
+        if state != animation.state {
+            animation.state = state;
+            animation.elapsed = 0.0;
+            animation.frame = None;
+        }
+    }
+}
+
+/// Steps each creature's animation player through the clip of its state, a whole frame at
+/// a time at the species' frame rate.
+pub(crate) fn play_animations(
+    time: Res<Time>,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    asset_server: Res<AssetServer>,
+    children: Query<&Children>,
+    mut players: Query<&mut AnimationPlayer>,
+    mut creatures: Query<(Entity, &SpeciesId, &mut CreatureAnimation), With<Creature>>,
+) {
+    for (entity, species_id, mut animation) in creatures.iter_mut() {
+        let animations = match library
+            .get(&catalogs, &species_id.0)
+            .and_then(|species| species.animations.as_ref())
+        {
+            Some(animations) => animations,
+            None => continue,
+        };
+
+        // the player turns up once the scene has been spawned, somewhere down its hierarchy
+        if animation
+            .player
+            .map_or(true, |player| players.get(player).is_err())
+        {
+            animation.player = find_player(entity, &children, &players);
+            animation.frame = None;
+        }
+        let mut player = match animation
+            .player
+            .and_then(|player| players.get_mut(player).ok())
+        {
+            Some(player) => player,
+            None => continue,
+        };
+
+        animation.elapsed += time.delta_seconds();
+        let frame = (animation.elapsed * animations.frame_rate) as u32;
+        if animation.frame == Some(frame) {
+            continue;
+        }
+        if animation.frame.is_none() {
+            // paused, so the clip only moves on when a new frame is due
+            player.play(asset_server.load(animations.clip(animation.state)));
+            player.repeat();
+            player.pause();
+        }
+        player.set_elapsed(frame as f32 / animations.frame_rate);
+        animation.frame = Some(frame);
+    }
+}
+
+fn find_player(
+    root: Entity,
+    children: &Query<&Children>,
+    players: &Query<&mut AnimationPlayer>,
+) -> Option<Entity> {
+    let mut stack = vec![root];
+    while let Some(entity) = stack.pop() {
+        if players.get(entity).is_ok() {
+            return Some(entity);
+        }
+        if let Ok(entity_children) = children.get(entity) {
+            stack.extend(entity_children.iter());
+        }
+    }
+    None
+}
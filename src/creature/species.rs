@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::planetoid::terrain::Biome;
+
+use super::model::AnimationState;
+
+/// Species catalogs loaded at startup. Species ids must be unique across all of them.
+const CATALOGS: [&str; 1] = ["species/default.species.ron"];
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Diet {
+    Herbivore,
+    Carnivore,
+    Omnivore,
+}
+
+/// Fractions of each need lost per day.
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct NeedsRates {
+    pub(crate) hunger: f32,
+    pub(crate) thirst: f32,
+    pub(crate) energy: f32,
+}
+
+/// Boids-style steering weights for species that move in herds.
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct Flocking {
+    /// Herd mates closer than this many radians are taken into account.
+    pub(crate) radius: f32,
+    /// How strongly herd mates keep out of each other's way.
+    pub(crate) separation: f32,
+    /// How strongly herd mates match each other's heading.
+    pub(crate) alignment: f32,
+    /// How strongly herd mates keep together.
+    pub(crate) cohesion: f32,
+}
+
+/// Skeletal animation clips of a species' model, one for each of a creature's
+/// `AnimationState`s.
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct Animations {
+    /// Frames per second the clips are stepped through, rather than played smoothly, so
+    /// the few pixels a creature covers don't shimmer.
+    #[serde(default = "default_frame_rate")]
+    pub(crate) frame_rate: f32,
+    pub(crate) idle: String,
+    pub(crate) walk: String,
+    pub(crate) eat: String,
+    pub(crate) sleep: String,
+}
+
+fn default_frame_rate() -> f32 {
+    6.0
+}
+
+impl Animations {
+    pub(crate) fn clip(&self, state: AnimationState) -> &str {
+        match state {
+            AnimationState::Idle => &self.idle,
+            AnimationState::Walk => &self.walk,
+            AnimationState::Eat => &self.eat,
+            AnimationState::Sleep => &self.sleep,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct Species {
+    pub(crate) name: String,
+    /// glTF scene the species is drawn with.
+    pub(crate) scene: String,
+    /// Species without them hold still in whatever pose their scene is in.
+    #[serde(default)]
+    pub(crate) animations: Option<Animations>,
+    /// Body colors individuals of the species are drawn from.
+    pub(crate) palette: Vec<[f32; 3]>,
+    /// Radians per second along the planetoid surface.
+    pub(crate) speed: f32,
+    pub(crate) diet: Diet,
+    pub(crate) preferred_biome: Biome,
+    /// In days.
+    pub(crate) lifespan: f32,
+    pub(crate) needs: NeedsRates,
+    /// Species without it move on their own.
+    #[serde(default)]
+    pub(crate) flocking: Option<Flocking>,
+}
+
+impl Diet {
+    pub(crate) fn meat_preference(self) -> f32 {
+        match self {
+            Diet::Herbivore => 0.0,
+            Diet::Omnivore => 0.5,
+            Diet::Carnivore => 1.0,
+        }
+    }
+}
+
+impl Species {
+    pub(crate) fn comfort_temperature(&self) -> f32 {
+        self.preferred_biome.temperature()
+    }
+
+    pub(crate) fn color(&self, variant: usize) -> Color {
+        self.palette
+            .get(variant % self.palette.len().max(1))
+            .map(|[r, g, b]| Color::rgb(*r, *g, *b))
+            .unwrap_or(Color::WHITE)
+    }
+}
+
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "5c0e43a1-7a2b-4f0e-9d51-3c1b2a8e6f10"]
+pub(crate) struct SpeciesCatalog {
+    pub(crate) species: HashMap<String, Species>,
+}
+
+#[derive(Default)]
+pub(crate) struct SpeciesLoader;
+
+impl AssetLoader for SpeciesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let catalog: SpeciesCatalog = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(catalog));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["species.ron"]
+    }
+}
+
+pub(crate) struct SpeciesLibrary {
+    catalogs: Vec<Handle<SpeciesCatalog>>,
+}
+
+impl SpeciesLibrary {
+    pub(crate) fn get<'a>(
+        &self,
+        catalogs: &'a Assets<SpeciesCatalog>,
+        id: &str,
+    ) -> Option<&'a Species> {
+        self.catalogs
+            .iter()
+            .filter_map(|handle| catalogs.get(handle))
+            .find_map(|catalog| catalog.species.get(id))
+    }
+
+    pub(crate) fn is_loaded(&self, catalogs: &Assets<SpeciesCatalog>) -> bool {
+        self.catalogs.iter().all(|handle| catalogs.contains(handle))
+    }
+
+    pub(crate) fn contains(&self, handle: &Handle<SpeciesCatalog>) -> bool {
+        self.catalogs.contains(handle)
+    }
+}
+
+#[derive(Component, Clone, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct SpeciesId(pub(crate) String);
+
+pub(crate) fn setup_species_library(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SpeciesLibrary {
+        catalogs: CATALOGS.iter().map(|path| asset_server.load(*path)).collect(),
+    });
+}
@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::DAY_LENGTH,
+    creature::{Creature, Needs},
+    plant::{Plant, PlantMatured},
+    state::AppState,
+};
+
+/// Water collected per day, from rain barrels nobody has to look after.
+const WATER_PER_DAY: f32 = 6.0;
+/// Nutrients composted per day.
+const NUTRIENTS_PER_DAY: f32 = 3.0;
+/// Seeds collected from each plant that matures.
+const SEEDS_PER_MATURED_PLANT: u32 = 1;
+/// Care points per day a perfectly content creature earns.
+const CARE_PER_CREATURE_PER_DAY: f32 = 4.0;
+/// Care points per day a perfectly healthy plant earns.
+const CARE_PER_PLANT_PER_DAY: f32 = 0.5;
+/// Creatures below this happiness, and plants below this health, earn nothing.
+const MIN_CONTENT: f32 = 0.5;
+
+pub(crate) struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Economy>()
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_economy))
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(collect_income));
+    }
+}
+
+/// An amount of everything the caretaker keeps in stock, whether that's what is stored,
+/// how much fits or what something costs. Kinds left out of a data file are none at all.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Supplies {
+    pub(crate) water: f32,
+    pub(crate) seeds: u32,
+    pub(crate) nutrients: f32,
+    /// Earned by keeping creatures happy and plants healthy.
+    pub(crate) care: f32,
+}
+
+impl Supplies {
+    pub(crate) const NONE: Supplies = Supplies {
+        water: 0.0,
+        seeds: 0,
+        nutrients: 0.0,
+        care: 0.0,
+    };
+
+    fn covers(&self, cost: &Supplies) -> bool {
+        self.water >= cost.water
+            && self.seeds >= cost.seeds
+            && self.nutrients >= cost.nutrients
+            && self.care >= cost.care
+    }
+}
+
+impl Default for Supplies {
+    fn default() -> Self {
+        Supplies::NONE
+    }
+}
+
+/// What the caretaker has in stock, and how much of it can be stored. The stock is shared
+/// by every planetoid in the system, so seeds gathered on one can be sown on another.
+pub(crate) struct Economy {
+    pub(crate) stock: Supplies,
+    pub(crate) capacity: Supplies,
+}
+
+impl Default for Economy {
+    fn default() -> Self {
+        Self {
+            stock: Supplies {
+                water: 10.0,
+                seeds: 10,
+                nutrients: 10.0,
+                care: 10.0,
+            },
+            capacity: Supplies {
+                water: 50.0,
+                seeds: 30,
+                nutrients: 30.0,
+                care: 999.0,
+            },
+        }
+    }
+}
+
+impl Economy {
+    pub(crate) fn can_afford(&self, cost: &Supplies) -> bool {
+        self.stock.covers(cost)
+    }
+
+    /// Takes the cost out of the stock if there is enough of everything.
+    pub(crate) fn spend(&mut self, cost: &Supplies) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        self.stock.water -= cost.water;
+        self.stock.seeds -= cost.seeds;
+        self.stock.nutrients -= cost.nutrients;
+        self.stock.care -= cost.care;
+        true
+    }
+
+    /// Adds to the stock, dropping whatever doesn't fit.
+    pub(crate) fn earn(&mut self, income: &Supplies) {
+        let (stock, capacity) = (&mut self.stock, &self.capacity);
+        stock.water = (stock.water + income.water).min(capacity.water);
+        stock.seeds = (stock.seeds + income.seeds).min(capacity.seeds);
+        stock.nutrients = (stock.nutrients + income.nutrients).min(capacity.nutrients);
+        stock.care = (stock.care + income.care).min(capacity.care);
+    }
+}
+
+fn reset_economy(mut economy: ResMut<Economy>) {
+    *economy = Economy::default();
+}
+
+/// How much above `MIN_CONTENT` a value is, scaled to `0.0..=1.0`.
+fn contentment(value: f32) -> f32 {
+    ((value - MIN_CONTENT) / (1.0 - MIN_CONTENT)).clamp(0.0, 1.0)
+}
+
+fn collect_income(
+    time: Res<Time>,
+    mut economy: ResMut<Economy>,
+    mut matured_events: EventReader<PlantMatured>,
+    creatures: Query<&Needs, With<Creature>>,
+    plants: Query<&Plant>,
+) {
+    let happiness: f32 = creatures
+        .iter()
+        .map(|needs| contentment((needs.hunger + needs.thirst + needs.energy) / 3.0))
+        .sum();
+    let plant_health: f32 = plants.iter().map(|plant| contentment(plant.health)).sum();
+    let care_per_day =
+        happiness * CARE_PER_CREATURE_PER_DAY + plant_health * CARE_PER_PLANT_PER_DAY;
+
+    let days = time.delta_seconds() / DAY_LENGTH;
+    let income = Supplies {
+        water: WATER_PER_DAY * days,
+        seeds: matured_events.iter().count() as u32 * SEEDS_PER_MATURED_PLANT,
+        nutrients: NUTRIENTS_PER_DAY * days,
+        care: care_per_day * days,
+    };
+    economy.earn(&income);
+}
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    clock::{GameClock, DAY_LENGTH},
+    creature::{
+        genetics::Genome,
+        life::{natural_death, CreatureDied, DeathCause},
+        model::Body,
+        species::{SpeciesCatalog, SpeciesId, SpeciesLibrary},
+        Creature, Goal, Needs,
+    },
+    planetoid::{
+        grid::SurfaceGrid,
+        heightfield::Heightfield,
+        terrain::{biome_at, Biome},
+        transform::{PlanetoidTransform, SurfaceIndex},
+        water::Water,
+        Planetoid,
+    },
+    plant::Plant,
+    state::{AppState, InGame},
+    GameWorldRenderLayer,
+};
+
+const SOIL_WIDTH: usize = 32;
+const SOIL_HEIGHT: usize = 16;
+/// Fraction of the gap to its natural fertility the soil makes up per day.
+const SOIL_RECOVERY_PER_DAY: f32 = 0.05;
+
+/// Creatures start looking for food below this hunger...
+const HUNGRY_BELOW: f32 = 0.6;
+/// ...and keep at it until they are this full.
+const SATED_ABOVE: f32 = 0.95;
+/// Creatures start looking for water below this thirst, and keep at it until sated.
+const THIRSTY_BELOW: f32 = 0.5;
+/// Farthest a creature notices water from, in radians.
+const WATER_SEARCH_RADIUS: f32 = 1.2;
+/// Thirst quenched per day while drinking.
+const DRINK_RATE: f32 = 3.0;
+/// Farthest a creature notices food from, in radians.
+const FORAGE_RADIUS: f32 = 0.8;
+/// Creatures can eat food this close, in radians.
+const EAT_RADIUS: f32 = 0.05;
+/// Diets below this meat preference don't bother with meat, and above one minus it
+/// don't bother with plants.
+const MIN_DIET_INTEREST: f32 = 0.3;
+
+/// Plant growth a creature eats per day.
+const GRAZE_RATE: f32 = 4.0;
+/// Hunger restored per unit of plant growth, for a pure herbivore.
+const PLANT_NUTRITION: f32 = 0.5;
+/// Meat a creature eats per day.
+const FEED_RATE: f32 = 4.0;
+/// Hunger restored per unit of meat, for a pure carnivore.
+const MEAT_NUTRITION: f32 = 0.5;
+/// Plants smaller than this aren't worth eating.
+const MIN_EDIBLE_GROWTH: f32 = 0.2;
+/// Predators only take on prey at most this large relative to themselves.
+const MAX_PREY_SIZE: f32 = 0.9;
+
+/// Meat left per unit of body size.
+const CORPSE_MEAT: f32 = 1.0;
+/// Meat that rots away per day.
+const DECAY_PER_DAY: f32 = 0.25;
+/// Fertility added to the soil per unit of rotted meat.
+const DECAY_FERTILITY: f32 = 0.8;
+/// Days of population samples kept.
+const HISTORY_LENGTH: usize = 64;
+
+pub(crate) struct EcosystemPlugin;
+
+impl Plugin for EcosystemPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PopulationHistory>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing).with_system(reset_population_history),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(setup_soil)
+                    .with_system(recover_soil)
+                    .with_system(update_hungry)
+                    .with_system(update_thirsty)
+                    .with_system(seek_water)
+                    .with_system(drink)
+                    .with_system(forage)
+                    .with_system(graze)
+                    .with_system(hunt.after(natural_death))
+                    .with_system(scavenge)
+                    .with_system(spawn_corpses)
+                    .with_system(decompose_corpses)
+                    .with_system(record_population),
+            );
+    }
+}
+
+/// How much plant life each part of a planetoid's surface can feed, from 0.0 to 1.0.
+#[derive(Component)]
+pub(crate) struct Soil {
+    pub(crate) fertility: SurfaceGrid<f32>,
+    /// What the soil recovers to when left alone.
+    natural: SurfaceGrid<f32>,
+}
+
+/// Marks creatures that are looking for food.
+#[derive(Component)]
+pub(crate) struct Hungry;
+
+/// Marks creatures that are looking for water, which comes before looking for food.
+#[derive(Component)]
+pub(crate) struct Thirsty;
+
+/// Remains of a dead creature, eaten by predators and rotting into the soil.
+#[derive(Component)]
+pub(crate) struct Corpse {
+    pub(crate) meat: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PopulationSample {
+    pub(crate) day: u32,
+    pub(crate) plants: usize,
+    pub(crate) corpses: usize,
+    pub(crate) species: HashMap<String, usize>,
+}
+
+/// One sample of every population per day, oldest first.
+#[derive(Default)]
+pub(crate) struct PopulationHistory {
+    pub(crate) samples: Vec<PopulationSample>,
+}
+
+fn natural_fertility(biome: Biome) -> f32 {
+    match biome {
+        Biome::Forest => 0.9,
+        Biome::Grassland => 0.7,
+        Biome::Highland => 0.4,
+        Biome::Tundra => 0.3,
+        Biome::Desert => 0.1,
+    }
+}
+
+fn reset_population_history(mut history: ResMut<PopulationHistory>) {
+    *history = PopulationHistory::default();
+}
+
+fn setup_soil(mut commands: Commands, planetoids: Query<(Entity, &Planetoid), Added<Planetoid>>) {
+    for (entity, planetoid) in planetoids.iter() {
+        let natural = SurfaceGrid::from_fn(SOIL_WIDTH, SOIL_HEIGHT, |sphere_coords| {
+            natural_fertility(biome_at(sphere_coords, &planetoid.params))
+        });
+        commands.entity(entity).insert(Soil {
+            fertility: natural.clone(),
+            natural,
+        });
+    }
+}
+
+fn recover_soil(time: Res<Time>, mut soil: Query<&mut Soil>) {
+    let recovery = (SOIL_RECOVERY_PER_DAY * time.delta_seconds() / DAY_LENGTH).min(1.0);
+    for mut soil in soil.iter_mut() {
+        let Soil { fertility, natural } = &mut *soil;
+        for (cell, natural) in fertility.cells_mut().iter_mut().zip(natural.cells()) {
+            // rotting corpses may push it above natural, which then slowly wears off
+            *cell += (natural - *cell) * recovery;
+        }
+    }
+}
+
+fn update_hungry(
+    mut commands: Commands,
+    query: Query<(Entity, &Needs, Option<&Hungry>), With<Creature>>,
+) {
+    for (entity, needs, hungry) in query.iter() {
+        if hungry.is_none() && needs.hunger < HUNGRY_BELOW {
+            commands.entity(entity).insert(Hungry);
+        } else if hungry.is_some() && needs.hunger > SATED_ABOVE {
+            commands.entity(entity).remove::<Hungry>();
+        }
+    }
+}
+
+fn update_thirsty(
+    mut commands: Commands,
+    query: Query<(Entity, &Needs, Option<&Thirsty>), With<Creature>>,
+) {
+    for (entity, needs, thirsty) in query.iter() {
+        if thirsty.is_none() && needs.thirst < THIRSTY_BELOW {
+            commands.entity(entity).insert(Thirsty);
+        } else if thirsty.is_some() && needs.thirst > SATED_ABOVE {
+            commands.entity(entity).remove::<Thirsty>();
+        }
+    }
+}
+
+/// Sends thirsty creatures to the nearest water, unless they're already on their way
+/// somewhere.
+fn seek_water(
+    planetoids: Query<(&Water, &Heightfield), With<Planetoid>>,
+    mut creatures: Query<
+        (&PlanetoidTransform, &Parent, &mut Goal),
+        (With<Creature>, With<Thirsty>),
+    >,
+) {
+    for (transform, parent, mut goal) in creatures.iter_mut() {
+        let (water, ground) = match planetoids.get(parent.get()) {
+            Ok(planetoid) => planetoid,
+            Err(_) => continue,
+        };
+        if goal.target.is_some() || water.can_drink_at(ground, transform.sphere_coords) {
+            continue;
+        }
+        goal.target = water.nearest_water(ground, transform.sphere_coords, WATER_SEARCH_RADIUS);
+    }
+}
+
+fn drink(
+    time: Res<Time>,
+    planetoids: Query<(&Water, &Heightfield), With<Planetoid>>,
+    mut creatures: Query<
+        (&PlanetoidTransform, &Parent, &mut Needs, &mut Goal),
+        (With<Creature>, With<Thirsty>),
+    >,
+) {
+    let sip = DRINK_RATE * time.delta_seconds() / DAY_LENGTH;
+    for (transform, parent, mut needs, mut goal) in creatures.iter_mut() {
+        let (water, ground) = match planetoids.get(parent.get()) {
+            Ok(planetoid) => planetoid,
+            Err(_) => continue,
+        };
+        if water.can_drink_at(ground, transform.sphere_coords) {
+            needs.thirst = (needs.thirst + sip).min(1.0);
+            // stay at the water until done drinking
+            if !goal.commanded {
+                goal.target = None;
+            }
+        }
+    }
+}
+
+fn eats_plants(genome: &Genome) -> bool {
+    genome.diet_preference < 1.0 - MIN_DIET_INTEREST
+}
+
+fn eats_meat(genome: &Genome) -> bool {
+    genome.diet_preference > MIN_DIET_INTEREST
+}
+
+fn can_prey_on(
+    (species, genome, transform): (&SpeciesId, &Genome, &PlanetoidTransform),
+    (prey_species, prey_transform): (&SpeciesId, &PlanetoidTransform),
+) -> bool {
+    eats_meat(genome)
+        && species.0 != prey_species.0
+        && prey_transform.scale <= transform.scale * MAX_PREY_SIZE
+}
+
+/// Sends hungry creatures towards the nearest food their diet allows, unless the player
+/// told them to go somewhere.
+fn forage(
+    index: Res<SurfaceIndex>,
+    mut creatures: Query<
+        (
+            Entity,
+            &SpeciesId,
+            &Genome,
+            &PlanetoidTransform,
+            &Parent,
+            &mut Goal,
+        ),
+        (With<Creature>, With<Hungry>, Without<Thirsty>),
+    >,
+    prey: Query<(&SpeciesId, &PlanetoidTransform), With<Creature>>,
+    plants: Query<(&Plant, &PlanetoidTransform)>,
+    corpses: Query<&PlanetoidTransform, With<Corpse>>,
+) {
+    for (entity, species, genome, transform, parent, mut goal) in creatures.iter_mut() {
+        if goal.commanded {
+            continue;
+        }
+
+        let food = index.nearest(
+            parent.get(),
+            transform.sphere_coords,
+            FORAGE_RADIUS,
+            |candidate| {
+                if let Ok((plant, _)) = plants.get(*candidate) {
+                    eats_plants(genome) && plant.growth >= MIN_EDIBLE_GROWTH
+                } else if corpses.get(*candidate).is_ok() {
+                    eats_meat(genome)
+                } else if let Ok(prey) = prey.get(*candidate) {
+                    *candidate != entity && can_prey_on((species, genome, transform), prey)
+                } else {
+                    false
+                }
+            },
+        );
+
+        goal.target = food.and_then(|(food, _)| {
+            plants
+                .get(food)
+                .map(|(_, food_transform)| food_transform)
+                .or_else(|_| corpses.get(food))
+                .or_else(|_| prey.get(food).map(|(_, food_transform)| food_transform))
+                .ok()
+                .map(|food_transform| food_transform.sphere_coords)
+        });
+    }
+}
+
+fn graze(
+    time: Res<Time>,
+    index: Res<SurfaceIndex>,
+    mut creatures: Query<
+        (&Genome, &PlanetoidTransform, &Parent, &mut Needs),
+        (With<Creature>, With<Hungry>),
+    >,
+    mut plants: Query<&mut Plant>,
+) {
+    let bite = GRAZE_RATE * time.delta_seconds() / DAY_LENGTH;
+    for (genome, transform, parent, mut needs) in creatures.iter_mut() {
+        if !eats_plants(genome) {
+            continue;
+        }
+
+        let plant = index
+            .nearest(
+                parent.get(),
+                transform.sphere_coords,
+                EAT_RADIUS,
+                |candidate| {
+                    plants
+                        .get(*candidate)
+                        .map_or(false, |plant| plant.growth > 0.0)
+                },
+            )
+            .and_then(|(plant, _)| plants.get_mut(plant).ok());
+        if let Some(mut plant) = plant {
+            let eaten = bite.min(plant.growth);
+            plant.growth -= eaten;
+            needs.hunger = (needs.hunger
+                + eaten * PLANT_NUTRITION * (1.0 - genome.diet_preference))
+                .min(1.0);
+        }
+    }
+}
+
+fn hunt(
+    mut commands: Commands,
+    index: Res<SurfaceIndex>,
+    mut died_events: ParamSet<(EventReader<CreatureDied>, EventWriter<CreatureDied>)>,
+    hunters: Query<
+        (Entity, &SpeciesId, &Genome, &PlanetoidTransform, &Parent),
+        (With<Creature>, With<Hungry>),
+    >,
+    prey: Query<(&SpeciesId, &PlanetoidTransform), With<Creature>>,
+) {
+    // creatures that died of natural causes this frame are only despawned at the end of it,
+    // so they neither hunt nor get killed a second time
+    let mut killed: Vec<Entity> = died_events.p0().iter().map(|event| event.entity).collect();
+    for (entity, species, genome, transform, parent) in hunters.iter() {
+        if killed.contains(&entity) {
+            continue;
+        }
+
+        let victim = index.nearest(
+            parent.get(),
+            transform.sphere_coords,
+            EAT_RADIUS,
+            |candidate| {
+                *candidate != entity
+                    && !killed.contains(candidate)
+                    && prey.get(*candidate).map_or(false, |prey| {
+                        can_prey_on((species, genome, transform), prey)
+                    })
+            },
+        );
+
+        // the kill leaves a corpse, which the hunter then feeds on
+        if let Some((victim, _)) = victim {
+            let (prey_species, prey_transform) = prey.get(victim).unwrap();
+            killed.push(victim);
+            commands.entity(victim).despawn_recursive();
+            died_events.p1().send(CreatureDied {
+                entity: victim,
+                species: prey_species.0.clone(),
+                planetoid: parent.get(),
+                sphere_coords: prey_transform.sphere_coords,
+                cause: DeathCause::Predation,
+            });
+        }
+    }
+}
+
+fn scavenge(
+    time: Res<Time>,
+    index: Res<SurfaceIndex>,
+    mut creatures: Query<
+        (&Genome, &PlanetoidTransform, &Parent, &mut Needs),
+        (With<Creature>, With<Hungry>),
+    >,
+    mut corpses: Query<&mut Corpse>,
+) {
+    let bite = FEED_RATE * time.delta_seconds() / DAY_LENGTH;
+    for (genome, transform, parent, mut needs) in creatures.iter_mut() {
+        if !eats_meat(genome) {
+            continue;
+        }
+
+        let corpse = index
+            .nearest(
+                parent.get(),
+                transform.sphere_coords,
+                EAT_RADIUS,
+                |candidate| {
+                    corpses
+                        .get(*candidate)
+                        .map_or(false, |corpse| corpse.meat > 0.0)
+                },
+            )
+            .and_then(|(corpse, _)| corpses.get_mut(corpse).ok());
+        if let Some(mut corpse) = corpse {
+            let eaten = bite.min(corpse.meat);
+            corpse.meat -= eaten;
+            needs.hunger =
+                (needs.hunger + eaten * MEAT_NUTRITION * genome.diet_preference).min(1.0);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_corpses(
+    mut commands: Commands,
+    mut died_events: EventReader<CreatureDied>,
+    library: Res<SpeciesLibrary>,
+    catalogs: Res<Assets<SpeciesCatalog>>,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut corpse_material: Local<Option<Handle<StandardMaterial>>>,
+) {
+    for event in died_events.iter() {
+        let species = match library.get(&catalogs, &event.species) {
+            Some(species) => species,
+            None => continue,
+        };
+
+        let material = corpse_material
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: Color::rgb(0.35, 0.3, 0.3),
+                    unlit: true,
+                    ..default()
+                })
+            })
+            .clone();
+
+        // the species' scene left in its rest pose, as nothing plays its animations
+        let corpse = commands
+            .spawn_bundle(SceneBundle {
+                scene: asset_server.load(&species.scene),
+                ..default()
+            })
+            .insert(Body(material))
+            .insert(PlanetoidTransform {
+                sphere_coords: event.sphere_coords,
+                rotation: 0.0,
+                scale: 0.6,
+            })
+            .insert(Corpse { meat: CORPSE_MEAT })
+            .insert(InGame)
+            .insert(game_world_render_layer.0)
+            .id();
+        commands.entity(event.planetoid).add_child(corpse);
+    }
+}
+
+fn decompose_corpses(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut soil: Query<&mut Soil, With<Planetoid>>,
+    mut corpses: Query<(Entity, &mut Corpse, &mut PlanetoidTransform, &Parent)>,
+) {
+    let decay = DECAY_PER_DAY * time.delta_seconds() / DAY_LENGTH;
+
+    for (entity, mut corpse, mut transform, parent) in corpses.iter_mut() {
+        let rotted = decay.min(corpse.meat);
+        corpse.meat -= rotted;
+        if let Ok(mut soil) = soil.get_mut(parent.get()) {
+            let fertility = soil.fertility.get_mut(transform.sphere_coords);
+            *fertility = (*fertility + rotted * DECAY_FERTILITY).min(1.0);
+        }
+
+        if corpse.meat <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            transform.scale = 0.2 + 0.4 * corpse.meat / CORPSE_MEAT;
+        }
+    }
+}
+
+fn record_population(
+    clock: Res<GameClock>,
+    mut history: ResMut<PopulationHistory>,
+    creatures: Query<&SpeciesId, With<Creature>>,
+    plants: Query<(), With<Plant>>,
+    corpses: Query<(), With<Corpse>>,
+) {
+    let day = clock.day();
+    if history.samples.last().map_or(false, |sample| sample.day == day) {
+        return;
+    }
+
+    let mut species = HashMap::new();
+    for species_id in creatures.iter() {
+        *species.entry(species_id.0.clone()).or_insert(0) += 1;
+    }
+
+    let sample = PopulationSample {
+        day,
+        plants: plants.iter().count(),
+        corpses: corpses.iter().count(),
+        species,
+    };
+    bevy::log::info!(
+        "day {}: {} plants, {} corpses, creatures {:?}",
+        sample.day,
+        sample.plants,
+        sample.corpses,
+        sample.species
+    );
+
+    history.samples.push(sample);
+    if history.samples.len() > HISTORY_LENGTH {
+        history.samples.remove(0);
+    }
+}
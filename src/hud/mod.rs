@@ -0,0 +1,235 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, SamplerDescriptor, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+
+use crate::state::AppState;
+
+use self::{
+    font::{glyph, text_width, ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH},
+    widgets::{
+        draw_announcement, draw_clock, draw_economy, draw_inspector, draw_objective,
+        draw_selected_needs, draw_system_view, draw_toolbar, queue_announcements, toggle_inspector,
+        Announcements, InspectorOpen,
+    },
+};
+
+pub(crate) mod font;
+mod widgets;
+
+pub(crate) type HudColor = [u8; 4];
+
+pub(crate) const HUD_TEXT: HudColor = [255, 241, 232, 255];
+pub(crate) const HUD_DIM: HudColor = [131, 118, 156, 255];
+pub(crate) const HUD_PANEL: HudColor = [0, 0, 0, 160];
+pub(crate) const HUD_GOOD: HudColor = [0, 228, 54, 255];
+pub(crate) const HUD_WARN: HudColor = [255, 163, 0, 255];
+pub(crate) const HUD_BAD: HudColor = [255, 0, 77, 255];
+
+pub(crate) struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HudSettings>()
+            .init_resource::<InspectorOpen>()
+            .init_resource::<Announcements>()
+            .add_event::<Announcement>()
+            .add_startup_system(setup_hud)
+            .add_system_to_stage(CoreStage::PreUpdate, clear_hud)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(draw_clock)
+                    .with_system(draw_economy)
+                    .with_system(draw_objective)
+                    .with_system(draw_selected_needs)
+                    .with_system(draw_toolbar)
+                    .with_system(draw_system_view)
+                    .with_system(toggle_inspector)
+                    .with_system(draw_inspector.after(toggle_inspector))
+                    .with_system(queue_announcements)
+                    .with_system(draw_announcement.after(queue_announcements)),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, upload_hud);
+    }
+}
+
+/// Resolution of the HUD, which is composited over the world by the post-processing chain.
+/// Keeping it equal to the world resolution makes both scale up in the same crisp pixels.
+pub(crate) struct HudSettings {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            width: 64,
+            height: 64,
+        }
+    }
+}
+
+/// A message shown across the HUD for a few seconds, one after the other.
+pub(crate) struct Announcement {
+    pub(crate) title: String,
+    pub(crate) text: String,
+    pub(crate) color: HudColor,
+}
+
+pub(crate) struct HudTexture(pub(crate) Handle<Image>);
+
+/// 5x5 pixel icon, rows top to bottom with the leftmost pixel in the highest bit.
+pub(crate) type Icon = [u8; 5];
+
+/// CPU side pixel buffer the HUD widgets draw into every frame.
+pub(crate) struct HudCanvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<HudColor>,
+}
+
+impl HudCanvas {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0; 4]; (width * height) as usize],
+        }
+    }
+
+    pub(crate) fn width(&self) -> i32 {
+        self.width as i32
+    }
+
+    pub(crate) fn height(&self) -> i32 {
+        self.height as i32
+    }
+
+    fn clear(&mut self) {
+        self.pixels.fill([0; 4]);
+    }
+
+    pub(crate) fn pixel(&mut self, x: i32, y: i32, color: HudColor) {
+        if x < 0 || y < 0 || x >= self.width() || y >= self.height() {
+            return;
+        }
+
+        let index = (y * self.width() + x) as usize;
+        let alpha = color[3] as u32;
+        if alpha == 255 {
+            self.pixels[index] = color;
+            return;
+        }
+
+        let under = self.pixels[index];
+        let mut blended = [0; 4];
+        for channel in 0..3 {
+            blended[channel] =
+                ((color[channel] as u32 * alpha + under[channel] as u32 * (255 - alpha)) / 255) as u8;
+        }
+        blended[3] = (alpha + under[3] as u32 * (255 - alpha) / 255) as u8;
+        self.pixels[index] = blended;
+    }
+
+    pub(crate) fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: HudColor) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.pixel(px, py, color);
+            }
+        }
+    }
+
+    /// Draws the text with its top left corner at `x`, `y` and returns the x after it.
+    pub(crate) fn text(&mut self, x: i32, y: i32, text: &str, color: HudColor) -> i32 {
+        let mut cursor = x;
+        for c in text.chars() {
+            let rows = glyph(c);
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.pixel(cursor + column as i32, y + row as i32, color);
+                    }
+                }
+            }
+            cursor += ADVANCE as i32;
+        }
+        cursor
+    }
+
+    pub(crate) fn text_centered(&mut self, y: i32, text: &str, color: HudColor) {
+        let x = (self.width() - text_width(text) as i32) / 2;
+        self.text(x, y, text, color);
+    }
+
+    /// Text on a translucent panel, so it stays readable over the planetoid.
+    pub(crate) fn label(&mut self, x: i32, y: i32, text: &str, color: HudColor) -> i32 {
+        self.rect(
+            x - 1,
+            y - 1,
+            text_width(text) as i32 + 2,
+            GLYPH_HEIGHT as i32 + 2,
+            HUD_PANEL,
+        );
+        self.text(x, y, text, color)
+    }
+
+    pub(crate) fn icon(&mut self, x: i32, y: i32, icon: &Icon, color: HudColor) {
+        for (row, bits) in icon.iter().enumerate() {
+            for column in 0..5 {
+                if bits & (1 << (4 - column)) != 0 {
+                    self.pixel(x + column, y + row as i32, color);
+                }
+            }
+        }
+    }
+
+    /// Horizontal meter for a value in `0.0..=1.0`, colored by how full it is.
+    pub(crate) fn bar(&mut self, x: i32, y: i32, width: i32, value: f32) {
+        let value = value.clamp(0.0, 1.0);
+        let color = if value > 0.6 {
+            HUD_GOOD
+        } else if value > 0.3 {
+            HUD_WARN
+        } else {
+            HUD_BAD
+        };
+
+        self.rect(x, y, width, 3, HUD_PANEL);
+        self.rect(x, y + 1, (value * width as f32).ceil() as i32, 1, color);
+    }
+}
+
+fn setup_hud(mut commands: Commands, settings: Res<HudSettings>, mut images: ResMut<Assets<Image>>) {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: settings.width,
+            height: settings.height,
+            ..default()
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
+        mag_filter: bevy::render::render_resource::FilterMode::Nearest,
+        min_filter: bevy::render::render_resource::FilterMode::Nearest,
+        ..default()
+    });
+
+    commands.insert_resource(HudTexture(images.add(image)));
+    commands.insert_resource(HudCanvas::new(settings.width, settings.height));
+}
+
+fn clear_hud(mut canvas: ResMut<HudCanvas>) {
+    canvas.clear();
+}
+
+fn upload_hud(canvas: Res<HudCanvas>, texture: Res<HudTexture>, mut images: ResMut<Assets<Image>>) {
+    if let Some(image) = images.get_mut(&texture.0) {
+        image.data.clear();
+        image.data.extend(canvas.pixels.iter().flatten());
+    }
+}
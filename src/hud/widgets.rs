@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    camera::CameraFocus,
+    clock::GameClock,
+    creature::{
+        genetics::{CreatureId, Genome, LineageBook},
+        Needs,
+    },
+    economy::{Economy, Supplies},
+    objectives::{ObjectiveBook, Objectives, Progress},
+    selection::Selected,
+    structures::BuildPlan,
+    tools::{ActiveTool, Tool, ToolCooldowns},
+};
+
+use super::{font::text_width, Announcement, HudCanvas, Icon, HUD_BAD, HUD_DIM, HUD_PANEL, HUD_TEXT};
+
+pub(crate) const ICON_SUN: Icon = [0b10101, 0b01110, 0b11111, 0b01110, 0b10101];
+pub(crate) const ICON_MOON: Icon = [0b01110, 0b11100, 0b11000, 0b11100, 0b01110];
+pub(crate) const ICON_FOOD: Icon = [0b00010, 0b01100, 0b11110, 0b11110, 0b01100];
+pub(crate) const ICON_WATER: Icon = [0b00100, 0b00100, 0b01110, 0b11111, 0b01110];
+pub(crate) const ICON_ENERGY: Icon = [0b00110, 0b01100, 0b11111, 0b00110, 0b01100];
+pub(crate) const ICON_SEED: Icon = [0b00000, 0b00100, 0b01110, 0b01110, 0b00100];
+pub(crate) const ICON_CARE: Icon = [0b01010, 0b11111, 0b11111, 0b01110, 0b00100];
+
+const ICON_FOOD_COLOR: [u8; 4] = [255, 119, 168, 255];
+const ICON_WATER_COLOR: [u8; 4] = [41, 173, 255, 255];
+const ICON_ENERGY_COLOR: [u8; 4] = [255, 236, 39, 255];
+const ICON_SUN_COLOR: [u8; 4] = [255, 236, 39, 255];
+const ICON_SEED_COLOR: [u8; 4] = [171, 82, 54, 255];
+const ICON_CARE_COLOR: [u8; 4] = [255, 0, 77, 255];
+
+/// Each kind of supply with its icon, and how much of it an amount holds, rounded down.
+fn supply_rows(supplies: &Supplies) -> [(&'static Icon, [u8; 4], u32); 4] {
+    [
+        (&ICON_WATER, ICON_WATER_COLOR, supplies.water as u32),
+        (&ICON_SEED, ICON_SEED_COLOR, supplies.seeds),
+        (&ICON_FOOD, ICON_FOOD_COLOR, supplies.nutrients as u32),
+        (&ICON_CARE, ICON_CARE_COLOR, supplies.care as u32),
+    ]
+}
+
+pub(crate) fn draw_clock(clock: Res<GameClock>, mut canvas: ResMut<HudCanvas>) {
+    let hour = clock.hour();
+    let (icon, color) = if (6..18).contains(&hour) {
+        (&ICON_SUN, ICON_SUN_COLOR)
+    } else {
+        (&ICON_MOON, HUD_DIM)
+    };
+
+    let end = canvas.label(1, 1, &format!("D{} {:02}H", clock.day() + 1, hour), HUD_TEXT);
+    canvas.rect(end - 1, 0, 7, 7, HUD_PANEL);
+    canvas.icon(end, 1, icon, color);
+}
+
+pub(crate) fn draw_selected_needs(
+    mut canvas: ResMut<HudCanvas>,
+    selected: Query<&Needs, With<Selected>>,
+) {
+    let needs = match selected.iter().next() {
+        Some(needs) => needs,
+        None => return,
+    };
+
+    let rows = [
+        (&ICON_FOOD, ICON_FOOD_COLOR, needs.hunger),
+        (&ICON_WATER, ICON_WATER_COLOR, needs.thirst),
+        (&ICON_ENERGY, ICON_ENERGY_COLOR, needs.energy),
+    ];
+
+    let top = canvas.height() - rows.len() as i32 * 6 - 1;
+    canvas.rect(0, top - 1, 24, rows.len() as i32 * 6 + 2, HUD_PANEL);
+    for (i, (icon, color, value)) in rows.into_iter().enumerate() {
+        let y = top + i as i32 * 6;
+        canvas.icon(1, y, icon, color);
+        canvas.bar(7, y + 1, 16, value);
+    }
+}
+
+/// The stock of every supply down the left edge, dimmed when its storage is full.
+pub(crate) fn draw_economy(economy: Res<Economy>, mut canvas: ResMut<HudCanvas>) {
+    let stock = supply_rows(&economy.stock);
+    let capacity = supply_rows(&economy.capacity);
+
+    let top = 8;
+    canvas.rect(0, top - 1, 19, stock.len() as i32 * 6 + 1, HUD_PANEL);
+    for (i, ((icon, color, amount), (_, _, limit))) in stock.into_iter().zip(capacity).enumerate() {
+        let y = top + i as i32 * 6;
+        let text_color = if amount >= limit { HUD_DIM } else { HUD_TEXT };
+        canvas.icon(1, y, icon, color);
+        canvas.text(7, y, &amount.to_string(), text_color);
+    }
+}
+
+/// The objective being worked towards in the top right corner, with how far along it is.
+pub(crate) fn draw_objective(
+    objectives: Res<Objectives>,
+    books: Res<Assets<ObjectiveBook>>,
+    progress: Res<Progress>,
+    mut canvas: ResMut<HudCanvas>,
+) {
+    let current = books.get(&objectives.0).and_then(|book| progress.current(book));
+    let (objective, fraction) = match current {
+        Some(current) => current,
+        None => return,
+    };
+
+    let width = text_width(&objective.title) as i32;
+    let x = canvas.width() - width - 1;
+    canvas.rect(x - 1, 0, width + 2, 10, HUD_PANEL);
+    canvas.text(x, 1, &objective.title, HUD_TEXT);
+    canvas.bar(x, 6, width, fraction);
+}
+
+/// The active tool in the bottom right corner, with its cooldown and what it costs, or for
+/// the build tool why the structure doesn't fit where the cursor is.
+pub(crate) fn draw_toolbar(
+    tool: Res<ActiveTool>,
+    cooldowns: Res<ToolCooldowns>,
+    economy: Res<Economy>,
+    plan: Res<BuildPlan>,
+    mut canvas: ResMut<HudCanvas>,
+) {
+    let (label, cost, error) = match tool.0 {
+        Tool::Build => (plan.kind.label(), plan.kind.cost(), plan.error),
+        tool => (tool.label(), tool.cost(), None),
+    };
+    let color = if economy.can_afford(&cost) && error.is_none() {
+        HUD_TEXT
+    } else {
+        HUD_BAD
+    };
+
+    let y = canvas.height() - 7;
+    let x = canvas.width() - 25;
+    canvas.rect(x - 1, y - 5, 26, 12, HUD_PANEL);
+    canvas.bar(x, y - 4, 24, cooldowns.progress(tool.0));
+    canvas.text(x, y, label, color);
+
+    let top = y - 12;
+    if let Some(error) = error {
+        canvas.rect(x - 1, top - 1, 26, 7, HUD_PANEL);
+        canvas.text(x, top, error.label(), HUD_BAD);
+        return;
+    }
+
+    // at most two kinds of supply fit next to each other
+    let costs: Vec<_> = supply_rows(&cost)
+        .into_iter()
+        .filter(|(_, _, amount)| *amount > 0)
+        .take(2)
+        .collect();
+    if costs.is_empty() {
+        return;
+    }
+    canvas.rect(x - 1, top - 1, 26, 7, HUD_PANEL);
+    let mut left = x;
+    for (icon, icon_color, amount) in costs {
+        canvas.icon(left, top, icon, icon_color);
+        left = canvas.text(left + 6, top, &amount.to_string(), HUD_TEXT);
+    }
+}
+
+/// Marks the system view below the objective, where clicking a planetoid flies over to it.
+pub(crate) fn draw_system_view(focus: Res<CameraFocus>, mut canvas: ResMut<HudCanvas>) {
+    if !focus.system_view {
+        return;
+    }
+    let width = text_width("SYSTEM") as i32;
+    canvas.label((canvas.width() - width) / 2, 12, "SYSTEM", HUD_TEXT);
+}
+
+/// Seconds each announcement stays up.
+const ANNOUNCEMENT_DURATION: f32 = 3.0;
+
+/// Announcements waiting to be shown, the first one being on screen for `shown_for` seconds.
+#[derive(Default)]
+pub(crate) struct Announcements {
+    queue: VecDeque<Announcement>,
+    shown_for: f32,
+}
+
+pub(crate) fn queue_announcements(
+    mut announcements: ResMut<Announcements>,
+    mut events: EventReader<Announcement>,
+) {
+    for event in events.iter() {
+        announcements.queue.push_back(Announcement {
+            title: event.title.clone(),
+            text: event.text.clone(),
+            color: event.color,
+        });
+    }
+}
+
+pub(crate) fn draw_announcement(
+    time: Res<Time>,
+    mut announcements: ResMut<Announcements>,
+    mut canvas: ResMut<HudCanvas>,
+) {
+    let announcement = match announcements.queue.front() {
+        Some(announcement) => announcement,
+        None => return,
+    };
+    canvas.rect(0, 18, canvas.width(), 14, HUD_PANEL);
+    canvas.text_centered(19, &announcement.title, announcement.color);
+    canvas.text_centered(25, &announcement.text, HUD_TEXT);
+
+    announcements.shown_for += time.delta_seconds();
+    if announcements.shown_for > ANNOUNCEMENT_DURATION {
+        announcements.queue.pop_front();
+        announcements.shown_for = 0.0;
+    }
+}
+
+/// Whether the genetics inspector for the selected creature is shown.
+#[derive(Default)]
+pub(crate) struct InspectorOpen(pub(crate) bool);
+
+pub(crate) fn toggle_inspector(keys: Res<Input<KeyCode>>, mut open: ResMut<InspectorOpen>) {
+    if keys.just_pressed(KeyCode::I) {
+        open.0 = !open.0;
+    }
+}
+
+fn format_parents(lineage: &LineageBook, id: CreatureId) -> String {
+    match lineage.get(id).and_then(|record| record.parents) {
+        Some((a, b)) => format!("{}+{}", a.0, b.0),
+        None => "-".to_string(),
+    }
+}
+
+pub(crate) fn draw_inspector(
+    open: Res<InspectorOpen>,
+    lineage: Res<LineageBook>,
+    mut canvas: ResMut<HudCanvas>,
+    selected: Query<(&CreatureId, &Genome), With<Selected>>,
+) {
+    if !open.0 {
+        return;
+    }
+
+    let (id, genome) = match selected.iter().next() {
+        Some(selected) => selected,
+        None => return,
+    };
+
+    let record = lineage.get(*id);
+    let generation = record.map_or(0, |record| record.generation);
+    let (parents, grandparents) = match record.and_then(|record| record.parents) {
+        Some((a, b)) => (
+            format!("P {}+{}", a.0, b.0),
+            format!("{} {}", format_parents(&lineage, a), format_parents(&lineage, b)),
+        ),
+        None => ("P -".to_string(), String::new()),
+    };
+
+    let left = canvas.width() - 33;
+    canvas.rect(left - 1, 8, 34, 40, HUD_PANEL);
+    canvas.text(left, 9, &format!("#{} G{}", id.0, generation), HUD_TEXT);
+    canvas.text(left, 15, &parents, HUD_DIM);
+    canvas.text(left, 21, &grandparents, HUD_DIM);
+
+    let [r, g, b, _] = genome.body_color().as_rgba_f32();
+    let swatch = [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255];
+    canvas.rect(canvas.width() - 5, 9, 4, 5, swatch);
+
+    let genes = [
+        ("SZ", (genome.size - 0.5) / 1.1),
+        ("SP", (genome.speed - 0.5) / 1.3),
+        ("TM", genome.temperature_tolerance),
+        ("MT", genome.diet_preference),
+    ];
+    for (i, (name, value)) in genes.into_iter().enumerate() {
+        let y = 27 + i as i32 * 5;
+        canvas.text(left, y, name, HUD_DIM);
+        canvas.bar(left + 9, y + 1, 23, value);
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    camera::CameraFocus,
+    clock::{GameClock, DAY_LENGTH},
+    creature::{life::CreatureBorn, Creature, Needs, SpawnCreature},
+    economy::{Economy, Supplies},
+    planetoid::{terrain::Biome, Planetoid},
+    plant::PlantMatured,
+    state::{AppState, WorldRng},
+    structures::{StructureBuilt, StructureKind},
+    tools::Tool,
+};
+
+const OBJECTIVES_PATH: &str = "objectives/default.objectives.ron";
+/// Creatures whose needs are at least this satisfied on average count as happy.
+const HAPPY_ABOVE: f32 = 0.7;
+/// Species unlocked by an objective arrive as a pair of young adults.
+const ARRIVAL_MATURITY: f32 = 0.3;
+/// Farthest apart the arriving pair lands, in normalized sphere coordinates.
+const ARRIVAL_SPREAD: f32 = 0.02;
+
+pub(crate) struct ObjectivePlugin;
+
+impl Plugin for ObjectivePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ObjectiveBook>()
+            .init_asset_loader::<ObjectiveLoader>()
+            .init_resource::<Progress>()
+            .init_resource::<Unlocks>()
+            .add_event::<ObjectiveCompleted>()
+            .add_startup_system(load_objectives)
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_progress))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(track_objectives)
+                    .with_system(grant_rewards.after(track_objectives)),
+            )
+            .add_system(update_unlocks);
+    }
+}
+
+/// What has to happen for an objective to be completed.
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) enum Task {
+    /// Plants growing up, anywhere or only in the given biome.
+    PlantsMatured {
+        count: u32,
+        #[serde(default)]
+        biome: Option<Biome>,
+    },
+    /// Creatures born to parents, of any species or only the given one.
+    CreaturesBorn {
+        count: u32,
+        #[serde(default)]
+        species: Option<String>,
+    },
+    StructuresBuilt {
+        count: u32,
+        #[serde(default)]
+        kind: Option<StructureKind>,
+    },
+    /// At least `count` creatures happy at once, for `days` days without a break.
+    HappyCreatures {
+        count: u32,
+        days: f32,
+    },
+    SurviveDays {
+        days: u32,
+    },
+}
+
+impl Task {
+    /// The progress the objective is completed at.
+    fn target(&self) -> f32 {
+        match self {
+            Task::PlantsMatured { count, .. }
+            | Task::CreaturesBorn { count, .. }
+            | Task::StructuresBuilt { count, .. } => *count as f32,
+            Task::HappyCreatures { days, .. } => *days,
+            Task::SurviveDays { days } => *days as f32,
+        }
+    }
+}
+
+/// Something only available once an objective rewarding it is completed.
+#[derive(Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Unlock {
+    Tool(Tool),
+    Structure(StructureKind),
+    /// A species that only arrives on the planetoid as a reward.
+    Species(String),
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+pub(crate) struct Reward {
+    #[serde(default)]
+    pub(crate) unlocks: Vec<Unlock>,
+    #[serde(default)]
+    pub(crate) supplies: Supplies,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct Objective {
+    pub(crate) id: String,
+    /// Short name that fits the HUD.
+    pub(crate) title: String,
+    pub(crate) task: Task,
+    /// Ids of the objectives that have to be completed before this one is tracked.
+    #[serde(default)]
+    pub(crate) requires: Vec<String>,
+    #[serde(default)]
+    pub(crate) reward: Reward,
+}
+
+/// Every objective of the game, in the order they are offered.
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "9a4f2c1e-3b7d-4e58-8c06-1d2e5f7a9b34"]
+pub(crate) struct ObjectiveBook {
+    pub(crate) objectives: Vec<Objective>,
+}
+
+#[derive(Default)]
+pub(crate) struct ObjectiveLoader;
+
+impl AssetLoader for ObjectiveLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let book: ObjectiveBook = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(book));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["objectives.ron"]
+    }
+}
+
+pub(crate) struct Objectives(pub(crate) Handle<ObjectiveBook>);
+
+/// Ids of the completed objectives, in the order they were completed, and how far along
+/// the ones being tracked are.
+#[derive(Default)]
+pub(crate) struct Progress {
+    pub(crate) completed: Vec<String>,
+    tracked: HashMap<String, f32>,
+}
+
+impl Progress {
+    pub(crate) fn is_completed(&self, id: &str) -> bool {
+        self.completed.iter().any(|completed| completed == id)
+    }
+
+    fn is_active(&self, objective: &Objective) -> bool {
+        !self.is_completed(&objective.id)
+            && objective.requires.iter().all(|id| self.is_completed(id))
+    }
+
+    /// The first objective being worked towards, and how far along it is from 0.0 to 1.0.
+    pub(crate) fn current<'a>(&self, book: &'a ObjectiveBook) -> Option<(&'a Objective, f32)> {
+        book.objectives
+            .iter()
+            .find(|objective| self.is_active(objective))
+            .map(|objective| {
+                let progress = self.tracked.get(&objective.id).copied().unwrap_or(0.0);
+                (
+                    objective,
+                    progress / objective.task.target().max(f32::EPSILON),
+                )
+            })
+    }
+}
+
+/// Whatever is still waiting on an objective to be completed. Anything not rewarded by any
+/// objective is available from the start.
+#[derive(Default)]
+pub(crate) struct Unlocks {
+    locked: HashSet<Unlock>,
+}
+
+impl Unlocks {
+    pub(crate) fn tool(&self, tool: Tool) -> bool {
+        !self.locked.contains(&Unlock::Tool(tool))
+    }
+
+    pub(crate) fn structure(&self, kind: StructureKind) -> bool {
+        !self.locked.contains(&Unlock::Structure(kind))
+    }
+}
+
+/// Sent when an objective is completed, or when a save with it completed is restored, in
+/// which case its supplies were already handed out.
+pub(crate) struct ObjectiveCompleted {
+    pub(crate) id: String,
+    pub(crate) restored: bool,
+}
+
+fn load_objectives(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Objectives(asset_server.load(OBJECTIVES_PATH)));
+}
+
+fn reset_progress(mut progress: ResMut<Progress>) {
+    *progress = Progress::default();
+}
+
+fn update_unlocks(
+    objectives: Res<Objectives>,
+    books: Res<Assets<ObjectiveBook>>,
+    progress: Res<Progress>,
+    mut unlocks: ResMut<Unlocks>,
+) {
+    let book = match books.get(&objectives.0) {
+        Some(book) => book,
+        None => return,
+    };
+
+    unlocks.locked = book
+        .objectives
+        .iter()
+        .filter(|objective| !progress.is_completed(&objective.id))
+        .flat_map(|objective| objective.reward.unlocks.iter().cloned())
+        .collect();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track_objectives(
+    time: Res<Time>,
+    clock: Res<GameClock>,
+    objectives: Res<Objectives>,
+    books: Res<Assets<ObjectiveBook>>,
+    mut progress: ResMut<Progress>,
+    mut matured_events: EventReader<PlantMatured>,
+    mut born_events: EventReader<CreatureBorn>,
+    mut built_events: EventReader<StructureBuilt>,
+    mut completed_events: EventWriter<ObjectiveCompleted>,
+    creatures: Query<&Needs, With<Creature>>,
+) {
+    let matured: Vec<Biome> = matured_events.iter().map(|event| event.biome).collect();
+    // founders, whether starting creatures or arrivals, weren't born here
+    let born: Vec<&str> = born_events
+        .iter()
+        .filter(|event| event.parents.is_some())
+        .map(|event| event.species.as_str())
+        .collect();
+    let built: Vec<StructureKind> = built_events.iter().map(|event| event.kind).collect();
+
+    let book = match books.get(&objectives.0) {
+        Some(book) => book,
+        None => return,
+    };
+
+    let days = time.delta_seconds() / DAY_LENGTH;
+    let happy = creatures
+        .iter()
+        .filter(|needs| (needs.hunger + needs.thirst + needs.energy) / 3.0 >= HAPPY_ABOVE)
+        .count() as u32;
+
+    for objective in book.objectives.iter() {
+        if !progress.is_active(objective) {
+            continue;
+        }
+
+        let tracked = progress.tracked.entry(objective.id.clone()).or_insert(0.0);
+        match &objective.task {
+            Task::PlantsMatured { biome, .. } => {
+                let count = matured
+                    .iter()
+                    .filter(|matured| biome.map_or(true, |biome| **matured == biome))
+                    .count();
+                *tracked += count as f32;
+            }
+            Task::CreaturesBorn { species, .. } => {
+                let count = born
+                    .iter()
+                    .filter(|born| {
+                        species
+                            .as_ref()
+                            .map_or(true, |species| **born == species.as_str())
+                    })
+                    .count();
+                *tracked += count as f32;
+            }
+            Task::StructuresBuilt { kind, .. } => {
+                let count = built
+                    .iter()
+                    .filter(|built| kind.map_or(true, |kind| **built == kind))
+                    .count();
+                *tracked += count as f32;
+            }
+            Task::HappyCreatures { count, .. } => {
+                *tracked = if happy >= *count {
+                    *tracked + days
+                } else {
+                    0.0
+                };
+            }
+            Task::SurviveDays { .. } => {
+                *tracked = clock.day() as f32;
+            }
+        }
+
+        if *tracked >= objective.task.target() {
+            bevy::log::info!("objective {} completed", objective.id);
+            progress.tracked.remove(&objective.id);
+            progress.completed.push(objective.id.clone());
+            completed_events.send(ObjectiveCompleted {
+                id: objective.id.clone(),
+                restored: false,
+            });
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn grant_rewards(
+    objectives: Res<Objectives>,
+    books: Res<Assets<ObjectiveBook>>,
+    focus: Res<CameraFocus>,
+    mut rng: ResMut<WorldRng>,
+    mut economy: ResMut<Economy>,
+    mut completed_events: EventReader<ObjectiveCompleted>,
+    mut spawn_events: EventWriter<SpawnCreature>,
+    planetoids: Query<(Entity, &Planetoid)>,
+) {
+    let book = match books.get(&objectives.0) {
+        Some(book) => book,
+        None => return,
+    };
+    // arrivals land on the planetoid the caretaker is looking at, or the first one
+    let landing_planetoid = focus.planetoid.or_else(|| {
+        planetoids
+            .iter()
+            .find(|(_, planetoid)| planetoid.index == 0)
+            .map(|(entity, _)| entity)
+    });
+    let landing_planetoid = match landing_planetoid {
+        Some(planetoid) => planetoid,
+        None => return,
+    };
+
+    for event in completed_events.iter() {
+        let objective = match book
+            .objectives
+            .iter()
+            .find(|objective| objective.id == event.id)
+        {
+            Some(objective) => objective,
+            None => continue,
+        };
+
+        if !event.restored {
+            economy.earn(&objective.reward.supplies);
+        }
+
+        // the world is regenerated when a save is restored, so arrivals come again
+        for unlock in objective.reward.unlocks.iter() {
+            if let Unlock::Species(species) = unlock {
+                let landing = Vec2::new(rng.0.gen_range(0.0..1.0), rng.0.gen_range(0.2..0.8));
+                for offset in [-ARRIVAL_SPREAD, ARRIVAL_SPREAD] {
+                    spawn_events.send(SpawnCreature {
+                        species: species.clone(),
+                        planetoid: landing_planetoid,
+                        sphere_coords: landing + Vec2::new(offset, 0.0),
+                        maturity: ARRIVAL_MATURITY,
+                        parents: None,
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+
+/// Values stored in equirectangular cells over the planetoid surface, addressed with
+/// normalized sphere coordinates. Columns wrap around in longitude, rows don't.
+#[derive(Clone, Debug)]
+pub(crate) struct SurfaceGrid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> SurfaceGrid<T> {
+    pub(crate) fn new(width: usize, height: usize, value: T) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+}
+
+impl<T> SurfaceGrid<T> {
+    pub(crate) fn from_fn(width: usize, height: usize, mut f: impl FnMut(Vec2) -> T) -> Self {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(f(Self::center_of(width, height, x, y)));
+            }
+        }
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    fn center_of(width: usize, height: usize, x: usize, y: usize) -> Vec2 {
+        Vec2::new(
+            (x as f32 + 0.5) / width as f32,
+            (y as f32 + 0.5) / height as f32,
+        )
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn index(&self, sphere_coords: Vec2) -> usize {
+        let x = (sphere_coords.x.rem_euclid(1.0) * self.width as f32) as usize;
+        let y = (sphere_coords.y.clamp(0.0, 1.0) * self.height as f32) as usize;
+        y.min(self.height - 1) * self.width + x.min(self.width - 1)
+    }
+
+    pub(crate) fn center(&self, index: usize) -> Vec2 {
+        Self::center_of(self.width, self.height, index % self.width, index / self.width)
+    }
+
+    pub(crate) fn get(&self, sphere_coords: Vec2) -> &T {
+        &self.cells[self.index(sphere_coords)]
+    }
+
+    pub(crate) fn get_mut(&mut self, sphere_coords: Vec2) -> &mut T {
+        let index = self.index(sphere_coords);
+        &mut self.cells[index]
+    }
+
+    pub(crate) fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    pub(crate) fn cells_mut(&mut self) -> &mut [T] {
+        &mut self.cells
+    }
+
+    /// The four cells sharing an edge with the given one, wrapping in longitude.
+    pub(crate) fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (width, height) = (self.width, self.height);
+        let (x, y) = (index % width, index / width);
+        let left = y * width + (x + width - 1) % width;
+        let right = y * width + (x + 1) % width;
+        let up = (y > 0).then(|| index - width);
+        let down = (y + 1 < height).then(|| index + width);
+        [Some(left), Some(right), up, down].into_iter().flatten()
+    }
+}
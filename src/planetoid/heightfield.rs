@@ -0,0 +1,316 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        primitives::Aabb,
+        render_resource::PrimitiveTopology,
+    },
+};
+
+use crate::state::WorldParams;
+
+use super::{
+    grid::SurfaceGrid,
+    terrain::surface_height,
+    transform::{great_circle_distance, normalized_sphere_to_cartesian},
+    Planetoid,
+};
+
+/// Cells of the heightfield around the equator and from pole to pole.
+const HEIGHTFIELD_WIDTH: usize = 96;
+const HEIGHTFIELD_HEIGHT: usize = 48;
+/// Terraforming can't push the surface further than this from the unit sphere.
+const MAX_HEIGHT: f32 = 0.12;
+
+/// Radial displacement of the planetoid surface from the unit sphere, which the planetoid
+/// mesh is built from. Starts out as the world's terrain noise and is edited by terraforming.
+#[derive(Component)]
+pub(crate) struct Heightfield {
+    heights: SurfaceGrid<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum TerrainBrush {
+    Raise,
+    Lower,
+    /// Evens out bumps, moving each cell towards the average of its neighbors.
+    Smooth,
+}
+
+/// Applies a brush to the terrain of a planetoid around a point.
+pub(crate) struct Terraform {
+    pub(crate) planetoid: Entity,
+    pub(crate) sphere_coords: Vec2,
+    /// In radians.
+    pub(crate) radius: f32,
+    /// Height change at the center for raising and lowering, or the fraction of the way
+    /// to the neighbors' average for smoothing.
+    pub(crate) strength: f32,
+    pub(crate) brush: TerrainBrush,
+}
+
+impl Heightfield {
+    pub(crate) fn new(params: &WorldParams) -> Self {
+        Self {
+            heights: SurfaceGrid::from_fn(HEIGHTFIELD_WIDTH, HEIGHTFIELD_HEIGHT, |sphere_coords| {
+                surface_height(sphere_coords, params)
+            }),
+        }
+    }
+
+    /// A heightfield over an existing grid, for surfaces other than the ground.
+    pub(crate) fn from_heights(heights: SurfaceGrid<f32>) -> Self {
+        Self { heights }
+    }
+
+    pub(crate) fn heights(&self) -> &SurfaceGrid<f32> {
+        &self.heights
+    }
+
+    /// Sets the height of some cells and updates the mesh built from the heightfield to match.
+    pub(crate) fn set_heights(&mut self, mesh: &mut Mesh, changes: &[(usize, f32)]) {
+        for (index, height) in changes {
+            self.heights.cells_mut()[*index] = *height;
+        }
+        let changed: Vec<usize> = changes.iter().map(|(index, _)| *index).collect();
+        self.update_mesh(mesh, &changed);
+    }
+
+    /// Height of the surface at the given point, interpolated between cells.
+    pub(crate) fn height_at(&self, sphere_coords: Vec2) -> f32 {
+        let (width, height) = (self.heights.width(), self.heights.height());
+        let x = sphere_coords.x.rem_euclid(1.0) * width as f32 - 0.5;
+        let y = (sphere_coords.y * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let column = |offset: f32| (x0 + offset).rem_euclid(width as f32) as usize;
+        let row = |offset: f32| ((y0 + offset) as usize).min(height - 1);
+        let cell = |column: usize, row: usize| self.heights.cells()[row * width + column];
+
+        let top = cell(column(0.0), row(0.0)) * (1.0 - fx) + cell(column(1.0), row(0.0)) * fx;
+        let bottom = cell(column(0.0), row(1.0)) * (1.0 - fx) + cell(column(1.0), row(1.0)) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Where a point on the surface actually is, in the planetoid's local space.
+    pub(crate) fn surface_point(&self, sphere_coords: Vec2) -> Vec3 {
+        normalized_sphere_to_cartesian(sphere_coords) * (1.0 + self.height_at(sphere_coords))
+    }
+
+    /// Applies the brush and returns the indices of the cells that changed.
+    fn apply(&mut self, terraform: &Terraform) -> Vec<usize> {
+        let affected: Vec<(usize, f32)> = (0..self.heights.cells().len())
+            .filter_map(|index| {
+                let distance =
+                    great_circle_distance(self.heights.center(index), terraform.sphere_coords);
+                // smooth falloff towards the edge of the brush
+                let t = 1.0 - distance / terraform.radius;
+                (t > 0.0).then(|| (index, t * t * (3.0 - 2.0 * t)))
+            })
+            .collect();
+
+        let targets: Vec<f32> = affected
+            .iter()
+            .map(|(index, falloff)| {
+                let current = self.heights.cells()[*index];
+                match terraform.brush {
+                    TerrainBrush::Raise => current + terraform.strength * falloff,
+                    TerrainBrush::Lower => current - terraform.strength * falloff,
+                    TerrainBrush::Smooth => {
+                        let (sum, count) = self
+                            .heights
+                            .neighbors(*index)
+                            .fold((0.0, 0), |(sum, count), neighbor| {
+                                (sum + self.heights.cells()[neighbor], count + 1)
+                            });
+                        let average = sum / count as f32;
+                        current + (average - current) * (terraform.strength * falloff).min(1.0)
+                    }
+                }
+            })
+            .collect();
+
+        for ((index, _), target) in affected.iter().zip(targets) {
+            self.heights.cells_mut()[*index] = target.clamp(-MAX_HEIGHT, MAX_HEIGHT);
+        }
+        affected.into_iter().map(|(index, _)| index).collect()
+    }
+
+    fn vertex_columns(&self) -> usize {
+        self.heights.width() + 1
+    }
+
+    fn vertex_rows(&self) -> usize {
+        self.heights.height() + 1
+    }
+
+    fn vertex_coords(&self, column: usize, row: usize) -> Vec2 {
+        Vec2::new(
+            column as f32 / self.heights.width() as f32,
+            row as f32 / self.heights.height() as f32,
+        )
+    }
+
+    fn vertex_position(&self, column: usize, row: usize) -> Vec3 {
+        let sphere_coords = self.vertex_coords(column, row);
+        if row == 0 || row == self.heights.height() {
+            // every vertex of a pole sits on the same spot, so give them all the same height
+            let cells = self.heights.width();
+            let first = if row == 0 { 0 } else { self.heights.cells().len() - cells };
+            let average = self.heights.cells()[first..first + cells].iter().sum::<f32>()
+                / cells as f32;
+            return normalized_sphere_to_cartesian(sphere_coords) * (1.0 + average);
+        }
+        self.surface_point(sphere_coords)
+    }
+
+    fn vertex_normal(&self, positions: &[[f32; 3]], column: usize, row: usize) -> Vec3 {
+        let columns = self.vertex_columns();
+        let position = |column: usize, row: usize| Vec3::from(positions[row * columns + column]);
+        let up = position(column, row).normalize();
+        if row == 0 || row == self.heights.height() {
+            return up;
+        }
+
+        // the seam column duplicates the first one, so step over it when wrapping
+        let width = self.heights.width();
+        let left = (column + width - 1) % width;
+        let right = (column + 1) % width;
+        let along = position(right, row) - position(left, row);
+        let across = position(column, row + 1) - position(column, row - 1);
+        let normal = along.cross(across).normalize_or_zero();
+        if normal.dot(up) < 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
+
+    /// A sphere mesh following the heightfield, with one quad per cell.
+    pub(crate) fn build_mesh(&self) -> Mesh {
+        let (columns, rows) = (self.vertex_columns(), self.vertex_rows());
+
+        let mut positions = Vec::with_capacity(columns * rows);
+        let mut uvs = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                positions.push(self.vertex_position(column, row).to_array());
+                uvs.push(self.vertex_coords(column, row).to_array());
+            }
+        }
+
+        let mut normals = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                normals.push(self.vertex_normal(&positions, column, row).to_array());
+            }
+        }
+
+        let mut indices = Vec::with_capacity((columns - 1) * (rows - 1) * 6);
+        for row in 0..rows - 1 {
+            for column in 0..columns - 1 {
+                let top_left = (row * columns + column) as u32;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + columns as u32;
+                let bottom_right = bottom_left + 1;
+                indices.extend([top_left, top_right, bottom_left]);
+                indices.extend([top_right, bottom_right, bottom_left]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+
+    /// Rebuilds the vertices of the mesh around the given cells, leaving the rest alone.
+    fn update_mesh(&self, mesh: &mut Mesh, changed_cells: &[usize]) {
+        let (width, height) = (self.heights.width(), self.heights.height());
+        let columns = self.vertex_columns();
+
+        // a cell moves the four vertices at its corners, and their normals depend on
+        // the vertices around them as well
+        let mut dirty = vec![false; columns * self.vertex_rows()];
+        for index in changed_cells {
+            let (x, y) = (index % width, index / width);
+            for row in y.saturating_sub(1)..=(y + 2).min(height) {
+                for offset in 0..4 {
+                    let column = (x + width + offset - 1) % width;
+                    dirty[row * columns + column] = true;
+                    if column == 0 {
+                        dirty[row * columns + width] = true;
+                    }
+                }
+            }
+
+            // a pole's height is the average of the whole cell row next to it, so every
+            // vertex of the pole moves, along with the normals of the row below it
+            let pole_rows = if y == 0 {
+                Some(0..=1)
+            } else if y == height - 1 {
+                Some(height - 1..=height)
+            } else {
+                None
+            };
+            for row in pole_rows.into_iter().flatten() {
+                dirty[row * columns..(row + 1) * columns].fill(true);
+            }
+        }
+
+        let positions = match mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            _ => return,
+        };
+        for (vertex, _) in dirty.iter().enumerate().filter(|(_, dirty)| **dirty) {
+            positions[vertex] = self
+                .vertex_position(vertex % columns, vertex / columns)
+                .to_array();
+        }
+        let positions = positions.clone();
+
+        if let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+        {
+            for (vertex, _) in dirty.iter().enumerate().filter(|(_, dirty)| **dirty) {
+                normals[vertex] = self
+                    .vertex_normal(&positions, vertex % columns, vertex / columns)
+                    .to_array();
+            }
+        }
+    }
+}
+
+pub(crate) fn terraform(
+    mut commands: Commands,
+    mut terraform_events: EventReader<Terraform>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut planetoids: Query<
+        (Entity, &mut Heightfield, &Handle<Mesh>, Option<&Children>),
+        With<Planetoid>,
+    >,
+) {
+    for event in terraform_events.iter() {
+        let (entity, mut heightfield, mesh, children) = match planetoids.get_mut(event.planetoid) {
+            Ok(planetoid) => planetoid,
+            Err(_) => continue,
+        };
+        let changed = heightfield.apply(event);
+        if changed.is_empty() {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            heightfield.update_mesh(mesh, &changed);
+        }
+
+        // bounds are only computed for entities without them, and raycasts and
+        // culling would otherwise miss raised terrain sticking out of the old ones
+        commands.entity(entity).remove::<Aabb>();
+        for child in children.into_iter().flatten() {
+            commands.entity(*child).remove::<Aabb>();
+        }
+    }
+}
@@ -1,72 +1,108 @@
-use std::f32::consts::PI;
-
-use bevy::{prelude::*, render::render_resource::Face};
+use bevy::{pbr::NotShadowCaster, prelude::*};
 use bevy_mod_raycast::RayCastMesh;
 
-use crate::{GameWorldRenderLayer, PlanetoidRaycastSet, Res};
+use crate::{
+    camera::CameraFocus,
+    clock::GameClock,
+    state::{AppState, InGame, WorldParams},
+    GameWorldRenderLayer, PlanetoidRaycastSet, Res,
+};
 
 use self::{
-    rendering::{update_material_sun_pos, PlanetoidMaterial},
-    transform::match_planetoid_transforms,
+    heightfield::{terraform, Heightfield, Terraform},
+    moon::{cast_eclipses, orbit_moons, raise_tides, setup_moons, Occluder},
+    rendering::PlanetoidMaterial,
+    sky::{setup_sky, update_sky},
+    transform::{
+        match_planetoid_transforms, normalized_sphere_to_cartesian, rebuild_surface_index,
+        SurfaceIndex,
+    },
+    water::{simulate_water, spawn_water_shell, Water},
 };
 
+pub(crate) mod grid;
+pub(crate) mod heightfield;
+pub(crate) mod moon;
 mod rendering;
+pub(crate) mod sky;
+pub(crate) mod spatial;
+pub(crate) mod terrain;
 pub mod transform;
+pub(crate) mod water;
+
+/// Planetoids of the system sit this far apart in a row.
+const PLANETOID_SPACING: f32 = 4.0;
+/// Radius of a planetoid's sphere, which the terrain only barely rises above or dips below.
+const PLANETOID_RADIUS: f32 = 1.0;
 
 pub struct PlanetoidPlugin;
 
 impl Plugin for PlanetoidPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(PlanetoidRotation(Quat::IDENTITY))
+        app.init_resource::<SurfaceIndex>()
+            .add_event::<Terraform>()
             .add_plugin(MaterialPlugin::<PlanetoidMaterial>::default())
-            .add_startup_system(setup_planetoid)
-            .add_startup_system(setup_sun)
-            .add_startup_system(setup_sky)
-            .add_system(update_material_sun_pos)
-            .add_system(match_planetoid_transforms)
-            .add_system(set_planetoid_rotation)
-            .add_system(planetoid_rotation)
-            .add_system(update_sun);
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(setup_planetoids)
+                    .with_system(setup_sun)
+                    .with_system(setup_sky),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(setup_moons)
+                    .with_system(orbit_moons.after(rotate_planetoids))
+                    .with_system(cast_eclipses.after(orbit_moons))
+                    .with_system(raise_tides)
+                    .with_system(terraform)
+                    .with_system(match_planetoid_transforms.after(terraform))
+                    .with_system(simulate_water.after(terraform).after(raise_tides))
+                    .with_system(rotate_planetoids)
+                    .with_system(update_sun)
+                    .with_system(update_sky),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, rebuild_surface_index);
     }
 }
 
+/// One of the worlds of the system. Everything living or built on it is its child, placed
+/// with a `PlanetoidTransform`.
 #[derive(Component)]
-pub(crate) struct Planetoid;
+pub(crate) struct Planetoid {
+    /// Position in the system, which saves refer to the planetoid by.
+    pub(crate) index: usize,
+    /// What the planetoid was generated from.
+    pub(crate) params: WorldParams,
+}
 
 #[derive(Component)]
 pub(crate) struct Sun;
 
-#[derive(Component)]
-pub(crate) struct Sky;
-
-pub(crate) struct PlanetoidRotation(pub(crate) Quat);
-
-fn planetoid_rotation(time: Res<Time>, mut rotation: ResMut<PlanetoidRotation>) {
-    *rotation = PlanetoidRotation(
-        rotation.0
-            * Quat::from_axis_angle(
-                Vec3::new(1.0, 1.0, 1.0).normalize(),
-                0.1 * time.delta_seconds(),
-            ),
-    );
+/// Whether the sun is above the horizon at a spot on the planetoid.
+pub(crate) fn in_daylight(planetoid: &Transform, sphere_coords: Vec2, sun: Vec3) -> bool {
+    (planetoid.rotation * normalized_sphere_to_cartesian(sphere_coords)).dot(sun) > 0.0
 }
 
-fn set_planetoid_rotation(
-    rotation: Res<PlanetoidRotation>,
-    mut query: Query<&mut Transform, With<Planetoid>>,
-) {
-    for mut transform in &mut query {
-        transform.rotation = rotation.0;
+fn rotate_planetoids(time: Res<Time>, mut query: Query<(&mut Transform, &Planetoid)>) {
+    for (mut transform, planetoid) in query.iter_mut() {
+        transform.rotate(Quat::from_axis_angle(
+            Vec3::new(1.0, 1.0, 1.0).normalize(),
+            planetoid.params.rotation_speed * time.delta_seconds(),
+        ));
     }
 }
 
-fn setup_planetoid(
+fn setup_planetoids(
     mut commands: Commands,
     game_world_render_layer: Res<GameWorldRenderLayer>,
+    params: Res<WorldParams>,
     asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<PlanetoidMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    const HALF_SIZE: f32 = 1.0;
+    // enough to cover the planetoid in view and everything standing on it
+    const HALF_SIZE: f32 = 1.3;
     commands
         .spawn_bundle(DirectionalLightBundle {
             directional_light: DirectionalLight {
@@ -84,23 +120,47 @@ fn setup_planetoid(
             },
             ..default()
         })
+        .insert(InGame)
         .insert(game_world_render_layer.0);
 
     let color_ramp: Handle<Image> = asset_server.load("textures/planet_color.png");
-
-    commands
-        .spawn_bundle(MaterialMeshBundle {
-            mesh: asset_server.load("models/planetoid.gltf#Mesh0/Primitive0"),
-            material: materials.add(PlanetoidMaterial {
-                color_ramp,
-                heightmap: asset_server.load("textures/planet_height.png"),
-                sun_info: Vec4::new(0.0, 10.0, 0.0, 1.0),
-            }),
-            ..default()
-        })
-        .insert(Planetoid)
-        .insert(game_world_render_layer.0)
-        .insert(RayCastMesh::<PlanetoidRaycastSet>::default());
+    for index in 0..params.planetoids.max(1) {
+        let params = params.planetoid(index);
+        let heightfield = Heightfield::new(&params);
+        let water = Water::new(&heightfield, &params);
+        let atmosphere = Vec3::from(params.atmosphere_color).extend(params.atmosphere_thickness);
+
+        let planetoid = commands
+            .spawn_bundle(MaterialMeshBundle {
+                mesh: meshes.add(heightfield.build_mesh()),
+                material: materials.add(PlanetoidMaterial {
+                    color_ramp: color_ramp.clone(),
+                    occluder: Vec4::ZERO,
+                    atmosphere,
+                }),
+                transform: Transform::from_translation(Vec3::X * index as f32 * PLANETOID_SPACING),
+                ..default()
+            })
+            .insert(Planetoid { index, params })
+            .insert(Occluder {
+                radius: PLANETOID_RADIUS,
+            })
+            .insert(InGame)
+            .insert(game_world_render_layer.0)
+            .insert(RayCastMesh::<PlanetoidRaycastSet>::default())
+            .id();
+
+        spawn_water_shell(
+            &mut commands,
+            planetoid,
+            &water,
+            &heightfield,
+            &game_world_render_layer,
+            &mut meshes,
+            &mut standard_materials,
+        );
+        commands.entity(planetoid).insert(heightfield).insert(water);
+    }
 }
 
 fn setup_sun(
@@ -120,39 +180,27 @@ fn setup_sun(
             transform: Transform::from_scale(Vec3::splat(0.5)),
             ..default()
         })
+        // the light comes from the sun, so it can't stand in its own way
+        .insert(NotShadowCaster)
         .insert(Sun)
+        .insert(InGame)
         .insert(game_world_render_layer.0);
 }
 
-fn update_sun(time: Res<Time>, mut query: Query<&mut Transform, With<Sun>>) {
-    for mut transform in &mut query {
-        let pos = Mat4::from_translation(Vec3::new(5.0, 0.0, 0.0));
-        let rot_y = Mat4::from_rotation_y(time.seconds_since_startup() as f32 * 0.25);
-        let rot_z = Mat4::from_rotation_z(PI / 4.0);
-        let new_transform = rot_z * rot_y * pos;
-
-        *transform = transform.with_translation(new_transform.to_scale_rotation_translation().2);
-    }
-}
-
-fn setup_sky(
-    mut commands: Commands,
-    game_world_render_layer: Res<GameWorldRenderLayer>,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+/// Keeps the sun circling whatever the camera is looking at, and its light shining from
+/// wherever the sun is, with the shadow map centered on the planetoid in view.
+fn update_sun(
+    clock: Res<GameClock>,
+    focus: Res<CameraFocus>,
+    mut suns: Query<&mut Transform, With<Sun>>,
+    mut lights: Query<&mut Transform, (With<DirectionalLight>, Without<Sun>)>,
 ) {
-    commands
-        .spawn_bundle(MaterialMeshBundle {
-            mesh: asset_server.load("models/sky.glb#Mesh0/Primitive0"),
-            material: materials.add(StandardMaterial {
-                base_color_texture: Some(asset_server.load("textures/sky.png")),
-                cull_mode: Some(Face::Front),
-                unlit: true,
-                ..default()
-            }),
-            transform: Transform::from_scale(Vec3::splat(10.0)),
-            ..default()
-        })
-        .insert(Sky)
-        .insert(game_world_render_layer.0);
+    let sun = clock.sun_direction();
+    for mut transform in &mut suns {
+        transform.translation = focus.pivot + sun * 5.0;
+    }
+    for mut transform in &mut lights {
+        *transform =
+            Transform::from_translation(focus.pivot).looking_at(focus.pivot - sun, Vec3::Y);
+    }
 }
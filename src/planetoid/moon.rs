@@ -0,0 +1,216 @@
+use std::{f32::consts::PI, ops::Range};
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    clock::{ecliptic, GameClock, DAY_LENGTH},
+    state::InGame,
+    GameWorldRenderLayer,
+};
+
+use super::{rendering::PlanetoidMaterial, water::Water, Planetoid};
+
+/// Distance from the planetoid's center, kept short of halfway to the next planetoid.
+const ORBIT_RADIUS: Range<f32> = 1.5..1.85;
+const MOON_RADIUS: Range<f32> = 0.1..0.2;
+/// Days each orbit takes.
+const ORBIT_PERIOD: Range<f32> = 1.5..4.0;
+/// Tilt of the orbit against the sun's, in radians. Small enough for eclipses to happen,
+/// large enough for them not to happen every orbit.
+const INCLINATION: Range<f32> = 0.0..0.15;
+/// Sea level change at spring tide for a moon of `TIDE_MOON_RADIUS` at `TIDE_ORBIT_RADIUS`.
+const TIDE_HEIGHT: f32 = 0.0015;
+const TIDE_MOON_RADIUS: f32 = 0.15;
+const TIDE_ORBIT_RADIUS: f32 = 1.7;
+/// Shades from the night side to the day side of a moon, for its phases to read on the
+/// palette.
+const MOON_RAMP: [[u8; 4]; 8] = [
+    [10, 10, 18, 255],
+    [18, 18, 30, 255],
+    [29, 29, 44, 255],
+    [40, 40, 58, 255],
+    [95, 87, 79, 255],
+    [131, 118, 156, 255],
+    [194, 195, 199, 255],
+    [255, 241, 232, 255],
+];
+
+/// A moon circling a planetoid. Where it is follows from the game clock alone, so it's back
+/// in the same spot after a load.
+#[derive(Component)]
+pub(crate) struct Moon {
+    pub(crate) planetoid: Entity,
+    pub(crate) radius: f32,
+    orbit_radius: f32,
+    /// Days per orbit.
+    period: f32,
+    inclination: f32,
+    /// Angle along the orbit when the clock started.
+    start_angle: f32,
+}
+
+impl Moon {
+    /// Where the moon is relative to its planetoid.
+    fn offset(&self, clock: &GameClock) -> Vec3 {
+        let angle = self.start_angle + clock.elapsed / DAY_LENGTH / self.period * PI * 2.0;
+        let orbit =
+            ecliptic() * Quat::from_rotation_x(self.inclination) * Quat::from_rotation_y(angle);
+        orbit * Vec3::X * self.orbit_radius
+    }
+
+    /// Sea level change on the planetoid, highest at new and full moon when the moon and the
+    /// sun pull together, and lowest at the quarters.
+    fn tide(&self, clock: &GameClock) -> f32 {
+        let strength = (self.radius / TIDE_MOON_RADIUS).powi(3)
+            * (TIDE_ORBIT_RADIUS / self.orbit_radius).powi(3);
+        let alignment = self.offset(clock).normalize().dot(clock.sun_direction());
+        // cos(2a) = 2cos²(a) - 1
+        TIDE_HEIGHT * strength * (2.0 * alignment * alignment - 1.0)
+    }
+}
+
+/// Blocks the sun for the bodies behind it, casting eclipses.
+#[derive(Component)]
+pub(crate) struct Occluder {
+    pub(crate) radius: f32,
+}
+
+pub(crate) fn setup_moons(
+    mut commands: Commands,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<PlanetoidMaterial>>,
+    planetoids: Query<(Entity, &Planetoid), Added<Planetoid>>,
+) {
+    let mut color_ramp = None;
+    for (entity, planetoid) in planetoids.iter() {
+        if planetoid.params.moons == 0 {
+            continue;
+        }
+        let color_ramp = color_ramp
+            .get_or_insert_with(|| {
+                images.add(Image::new(
+                    Extent3d {
+                        width: MOON_RAMP.len() as u32,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    TextureDimension::D2,
+                    MOON_RAMP.concat(),
+                    TextureFormat::Rgba8UnormSrgb,
+                ))
+            })
+            .clone();
+
+        // like the plants, moons only depend on the planetoid they belong to
+        let mut rng = SmallRng::seed_from_u64(planetoid.params.seed.wrapping_add(1));
+        for _ in 0..planetoid.params.moons {
+            let moon = Moon {
+                planetoid: entity,
+                radius: rng.gen_range(MOON_RADIUS),
+                orbit_radius: rng.gen_range(ORBIT_RADIUS),
+                period: rng.gen_range(ORBIT_PERIOD),
+                inclination: rng.gen_range(INCLINATION),
+                start_angle: rng.gen_range(0.0..PI * 2.0),
+            };
+
+            commands
+                .spawn_bundle(MaterialMeshBundle {
+                    mesh: meshes.add(Mesh::from(shape::Icosphere {
+                        radius: moon.radius,
+                        subdivisions: 2,
+                    })),
+                    material: materials.add(PlanetoidMaterial {
+                        color_ramp: color_ramp.clone(),
+                        occluder: Vec4::ZERO,
+                        atmosphere: Vec4::ZERO,
+                    }),
+                    ..default()
+                })
+                .insert(Occluder {
+                    radius: moon.radius,
+                })
+                // eclipses are cast by the shader, which a shadow map would only double up
+                .insert(NotShadowCaster)
+                .insert(moon)
+                .insert(InGame)
+                .insert(game_world_render_layer.0);
+        }
+    }
+}
+
+pub(crate) fn orbit_moons(
+    clock: Res<GameClock>,
+    planetoids: Query<&Transform, (With<Planetoid>, Without<Moon>)>,
+    mut moons: Query<(&Moon, &mut Transform)>,
+) {
+    for (moon, mut transform) in moons.iter_mut() {
+        if let Ok(planetoid) = planetoids.get(moon.planetoid) {
+            transform.translation = planetoid.translation + moon.offset(&clock);
+        }
+    }
+}
+
+/// Hands every body the one most in the way of its sunlight, for the shader to darken
+/// whatever lies in its shadow.
+pub(crate) fn cast_eclipses(
+    clock: Res<GameClock>,
+    mut materials: ResMut<Assets<PlanetoidMaterial>>,
+    bodies: Query<(Entity, &Handle<PlanetoidMaterial>, &Transform, &Occluder)>,
+) {
+    let sun = clock.sun_direction();
+    for (entity, handle, transform, occluder) in bodies.iter() {
+        // how far off the line to the sun each other body is, relative to how far it has to
+        // be to miss this body altogether
+        let closest = bodies
+            .iter()
+            .filter(|(other, ..)| *other != entity)
+            .filter_map(|(_, _, other_transform, other_occluder)| {
+                let towards = other_transform.translation - transform.translation;
+                let along = towards.dot(sun);
+                let miss =
+                    (towards - sun * along).length() / (occluder.radius + other_occluder.radius);
+                if along > 0.0 && miss < 1.0 {
+                    Some((miss, other_transform.translation, other_occluder.radius))
+                } else {
+                    None
+                }
+            })
+            .min_by(|(a, ..), (b, ..)| a.total_cmp(b));
+
+        let occluder = match closest {
+            Some((_, position, radius)) => position.extend(radius),
+            None => Vec4::ZERO,
+        };
+        // writing marks the material for a re-upload, so only do it when the shadow moves
+        if materials
+            .get(handle)
+            .map_or(false, |material| material.occluder != occluder)
+        {
+            if let Some(material) = materials.get_mut(handle) {
+                material.occluder = occluder;
+            }
+        }
+    }
+}
+
+pub(crate) fn raise_tides(
+    clock: Res<GameClock>,
+    moons: Query<&Moon>,
+    mut planetoids: Query<(Entity, &mut Water), With<Planetoid>>,
+) {
+    for (entity, mut water) in planetoids.iter_mut() {
+        let tide = moons
+            .iter()
+            .filter(|moon| moon.planetoid == entity)
+            .map(|moon| moon.tide(&clock))
+            .sum();
+        water.set_tide(tide);
+    }
+}
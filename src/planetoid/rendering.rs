@@ -1,5 +1,3 @@
-use std::f32::consts::PI;
-
 use bevy::{prelude::*, reflect::TypeUuid, render::render_resource::AsBindGroup};
 
 #[derive(Default, AsBindGroup, TypeUuid, Debug, Clone, Component)]
@@ -8,11 +6,14 @@ pub struct PlanetoidMaterial {
     #[texture(0)]
     #[sampler(1)]
     pub color_ramp: Handle<Image>,
-    #[texture(2)]
-    #[sampler(3)]
-    pub heightmap: Handle<Image>,
-    #[uniform(4)]
-    pub sun_info: Vec4,
+    /// Position and radius of the body casting its shadow over this one, if the radius is
+    /// above zero. Everything else about the sun comes from the scene's directional light.
+    #[uniform(2)]
+    pub occluder: Vec4,
+    /// Color of the air in rgb and its thickness in w, which scatters the sunlight along the
+    /// rim and across the terminator. Airless bodies leave it at zero.
+    #[uniform(3)]
+    pub atmosphere: Vec4,
 }
 
 impl Material for PlanetoidMaterial {
@@ -24,23 +25,3 @@ impl Material for PlanetoidMaterial {
         "shaders/planetoid_material.wgsl".into()
     }
 }
-
-pub fn update_material_sun_pos(
-    time: Res<Time>,
-    mut materials: ResMut<Assets<PlanetoidMaterial>>,
-    query: Query<&Handle<PlanetoidMaterial>>,
-) {
-    for handle in query.iter() {
-        let mat = &mut materials.get_mut(handle);
-
-        if let Some(mat) = mat {
-            let pos = Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0));
-            let rot_y = Mat4::from_rotation_y(time.seconds_since_startup() as f32 * 0.25);
-            let rot_z = Mat4::from_rotation_z(PI / 4.0);
-            let transform = rot_z * rot_y * pos;
-            let info = transform.to_scale_rotation_translation().2;
-
-            mat.sun_info = Vec4::new(info.x, info.y, info.z, mat.sun_info.w);
-        }
-    }
-}
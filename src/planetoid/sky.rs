@@ -0,0 +1,135 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        mesh::Indices,
+        render_resource::{Face, PrimitiveTopology},
+    },
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    camera::CameraFocus,
+    clock::{ecliptic, GameClock},
+    state::{InGame, WorldParams},
+    GameWorldRenderLayer,
+};
+
+use super::transform::normalized_sphere_to_cartesian;
+
+const SKY_SCALE: f32 = 10.0;
+/// Just inside the sky, so the stars are drawn over it.
+const STARS_SCALE: f32 = 9.5;
+const STAR_COUNT: usize = 400;
+/// Half the width of a star on the unit sphere, about a pixel once rendered.
+const STAR_SIZE: f32 = 0.008;
+/// Colors along `textures/sky_stars.png`.
+const STAR_COLORS: usize = 8;
+
+/// Both the sky and the stars in front of it.
+#[derive(Component)]
+pub(crate) struct Sky;
+
+pub(crate) fn setup_sky(
+    mut commands: Commands,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    params: Res<WorldParams>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn_bundle(MaterialMeshBundle {
+            mesh: asset_server.load("models/sky.glb#Mesh0/Primitive0"),
+            material: materials.add(StandardMaterial {
+                base_color_texture: Some(asset_server.load("textures/sky.png")),
+                cull_mode: Some(Face::Front),
+                unlit: true,
+                ..default()
+            }),
+            transform: Transform::from_scale(Vec3::splat(SKY_SCALE)),
+            ..default()
+        })
+        // wrapped around everything, it would put the whole system in its shadow
+        .insert(NotShadowCaster)
+        .insert(Sky)
+        .insert(InGame)
+        .insert(game_world_render_layer.0);
+
+    commands
+        .spawn_bundle(MaterialMeshBundle {
+            mesh: meshes.add(build_stars(params.seed)),
+            material: materials.add(StandardMaterial {
+                base_color_texture: Some(asset_server.load("textures/sky_stars.png")),
+                cull_mode: None,
+                unlit: true,
+                ..default()
+            }),
+            transform: Transform::from_scale(Vec3::splat(STARS_SCALE)),
+            ..default()
+        })
+        .insert(NotShadowCaster)
+        .insert(Sky)
+        .insert(InGame)
+        .insert(game_world_render_layer.0);
+}
+
+/// Scatters small quads over the unit sphere, each picking one color of the star ramp.
+fn build_stars(seed: u64) -> Mesh {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut positions = Vec::with_capacity(STAR_COUNT * 4);
+    let mut normals = Vec::with_capacity(STAR_COUNT * 4);
+    let mut uvs = Vec::with_capacity(STAR_COUNT * 4);
+    let mut indices = Vec::with_capacity(STAR_COUNT * 6);
+    for star in 0..STAR_COUNT {
+        // uniform over the sphere rather than bunched up at the poles
+        let sphere_coords = Vec2::new(
+            rng.gen_range(0.0..1.0),
+            f32::acos(rng.gen_range(-1.0..1.0)) / PI,
+        );
+        let center = normalized_sphere_to_cartesian(sphere_coords);
+        let axis = if center.y.abs() < 0.9 {
+            Vec3::Y
+        } else {
+            Vec3::X
+        };
+        let right = center.cross(axis).normalize() * STAR_SIZE;
+        let up = center.cross(right);
+        // the middle of a texel, so every corner of the quad samples the same color
+        let uv = [
+            (rng.gen_range(0..STAR_COLORS) as f32 + 0.5) / STAR_COLORS as f32,
+            0.5,
+        ];
+
+        for corner in [-right - up, right - up, right + up, -right + up] {
+            positions.push((center + corner).to_array());
+            normals.push((-center).to_array());
+            uvs.push(uv);
+        }
+        let first = star as u32 * 4;
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Keeps the sky centered on whatever the camera is looking at, turning with the sun so the
+/// sun holds its place among the stars. The planetoids spin on their own against it.
+pub(crate) fn update_sky(
+    clock: Res<GameClock>,
+    focus: Res<CameraFocus>,
+    mut query: Query<&mut Transform, With<Sky>>,
+) {
+    let rotation = ecliptic() * Quat::from_rotation_y(clock.sun_angle());
+    for mut transform in &mut query {
+        transform.translation = focus.pivot;
+        transform.rotation = rotation;
+    }
+}
@@ -0,0 +1,461 @@
+//! Buckets points on the unit sphere for proximity queries by great-circle distance.
+//!
+//! Cells are latitude bands split into a number of columns proportional to the band's
+//! circumference, so they all cover roughly the same area. This module only depends on
+//! bevy's math types, so the benchmarks can include it directly.
+
+use std::f32::consts::PI;
+
+use bevy::math::Vec3;
+
+struct Entry<T> {
+    position: Vec3,
+    item: T,
+}
+
+pub(crate) struct SpatialIndex<T> {
+    /// Index of the first cell of each latitude band, with one extra for the end.
+    band_offsets: Vec<usize>,
+    cells: Vec<Vec<Entry<T>>>,
+    len: usize,
+}
+
+impl<T> SpatialIndex<T> {
+    /// An empty index with `bands` latitude bands, each about `PI / bands` radians tall.
+    pub(crate) fn new(bands: usize) -> Self {
+        let bands = bands.max(1);
+        let mut band_offsets = Vec::with_capacity(bands + 1);
+        let mut cells = 0;
+        for band in 0..bands {
+            band_offsets.push(cells);
+            let latitude = (band as f32 + 0.5) / bands as f32 * PI;
+            cells += ((2 * bands) as f32 * latitude.sin()).round().max(1.0) as usize;
+        }
+        band_offsets.push(cells);
+
+        Self {
+            band_offsets,
+            cells: (0..cells).map(|_| Vec::new()).collect(),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Empties the index, keeping its allocations for the next rebuild.
+    pub(crate) fn clear(&mut self) {
+        for cell in &mut self.cells {
+            cell.clear();
+        }
+        self.len = 0;
+    }
+
+    /// Adds an item at the given point, which is projected onto the unit sphere.
+    pub(crate) fn insert(&mut self, position: Vec3, item: T) {
+        let position = position.normalize_or_zero();
+        let (polar, azimuth) = spherical(position);
+        let band = self.band(polar);
+        let cell = self.band_offsets[band] + self.column(band, azimuth);
+        self.cells[cell].push(Entry { position, item });
+        self.len += 1;
+    }
+
+    /// Calls `f` with every item at most `radius` radians from `position`, along with the
+    /// item's position and distance.
+    pub(crate) fn for_each_within(
+        &self,
+        position: Vec3,
+        radius: f32,
+        mut f: impl FnMut(&T, Vec3, f32),
+    ) {
+        self.for_each_entry_within(position, radius, |entry, distance| {
+            f(&entry.item, entry.position, distance)
+        });
+    }
+
+    /// The item nearest to `position` that passes `filter`, at most `max_radius` radians
+    /// away, with its distance.
+    pub(crate) fn nearest(
+        &self,
+        position: Vec3,
+        max_radius: f32,
+        mut filter: impl FnMut(&T) -> bool,
+    ) -> Option<(&T, f32)> {
+        let mut best: Option<(&T, f32)> = None;
+
+        // search growing caps, as most queries find something close by
+        let mut radius = (PI / self.bands() as f32).min(max_radius);
+        loop {
+            self.for_each_entry_within(position, radius, |entry, distance| {
+                if best.map_or(true, |(_, best)| distance < best) && filter(&entry.item) {
+                    best = Some((&entry.item, distance));
+                }
+            });
+            if best.is_some() || radius >= max_radius {
+                return best;
+            }
+            radius = (radius * 2.0).min(max_radius);
+        }
+    }
+
+    /// Every item at most `radius` radians from `position` whose direction along the surface
+    /// is within `half_angle` radians of `facing`, a tangent at `position`.
+    pub(crate) fn within_cone(
+        &self,
+        position: Vec3,
+        facing: Vec3,
+        half_angle: f32,
+        radius: f32,
+    ) -> Vec<(&T, f32)> {
+        let position = position.normalize_or_zero();
+        let facing = (facing - position * facing.dot(position)).normalize_or_zero();
+        let min_dot = half_angle.min(PI).cos();
+
+        let mut found = Vec::new();
+        self.for_each_entry_within(position, radius, |entry, distance| {
+            let direction = (entry.position - position * entry.position.dot(position))
+                .normalize_or_zero();
+            // an item right on top of the query point counts as in front
+            if direction == Vec3::ZERO || direction.dot(facing) >= min_dot {
+                found.push((&entry.item, distance));
+            }
+        });
+        found
+    }
+
+    /// Every item at most `radius` radians from `position`, with its distance.
+    pub(crate) fn within(&self, position: Vec3, radius: f32) -> Vec<(&T, f32)> {
+        let mut found = Vec::new();
+        self.for_each_entry_within(position, radius, |entry, distance| {
+            found.push((&entry.item, distance));
+        });
+        found
+    }
+
+    fn for_each_entry_within<'a>(
+        &'a self,
+        position: Vec3,
+        radius: f32,
+        mut f: impl FnMut(&'a Entry<T>, f32),
+    ) {
+        let position = position.normalize_or_zero();
+        let min_dot = radius.min(PI).cos();
+        let (polar, azimuth) = spherical(position);
+
+        let first_band = self.band((polar - radius).max(0.0));
+        let last_band = self.band((polar + radius).min(PI));
+
+        // half the longitude span of the query cap, unless it covers a pole
+        let span = if polar - radius <= 0.0 || polar + radius >= PI || radius >= polar.sin() {
+            None
+        } else {
+            Some((radius.sin() / polar.sin()).asin())
+        };
+
+        for band in first_band..=last_band {
+            let columns = self.columns(band);
+            let (first, count) = match span {
+                Some(span) => {
+                    let first = self.column_unwrapped(band, azimuth - span);
+                    let last = self.column_unwrapped(band, azimuth + span);
+                    (first, ((last - first + 1) as usize).min(columns))
+                }
+                None => (0, columns),
+            };
+
+            for column in 0..count {
+                let column = (first + column as isize).rem_euclid(columns as isize) as usize;
+                for entry in &self.cells[self.band_offsets[band] + column] {
+                    let dot = entry.position.dot(position);
+                    if dot >= min_dot {
+                        f(entry, dot.clamp(-1.0, 1.0).acos());
+                    }
+                }
+            }
+        }
+    }
+
+    fn bands(&self) -> usize {
+        self.band_offsets.len() - 1
+    }
+
+    fn band(&self, polar: f32) -> usize {
+        ((polar / PI * self.bands() as f32) as usize).min(self.bands() - 1)
+    }
+
+    fn columns(&self, band: usize) -> usize {
+        self.band_offsets[band + 1] - self.band_offsets[band]
+    }
+
+    /// Column of the azimuth without wrapping it into the band, so ranges stay ordered.
+    fn column_unwrapped(&self, band: usize, azimuth: f32) -> isize {
+        (azimuth / (2.0 * PI) * self.columns(band) as f32).floor() as isize
+    }
+
+    fn column(&self, band: usize, azimuth: f32) -> usize {
+        self.column_unwrapped(band, azimuth).rem_euclid(self.columns(band) as isize) as usize
+    }
+}
+
+/// Polar angle from +Y in `0..=PI` and azimuth around Y in `0..2 * PI` of a unit vector.
+fn spherical(position: Vec3) -> (f32, f32) {
+    let polar = position.y.clamp(-1.0, 1.0).acos();
+    let azimuth = position.z.atan2(position.x).rem_euclid(2.0 * PI);
+    (polar, azimuth)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use bevy::math::Vec3;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::SpatialIndex;
+
+    const BANDS: usize = 16;
+    const RADII: [f32; 5] = [0.02, 0.1, 0.4, 1.5, PI];
+
+    /// A unit vector from a polar angle from +Y and an azimuth around it, as `spherical`
+    /// measures them.
+    fn point(polar: f32, azimuth: f32) -> Vec3 {
+        Vec3::new(
+            polar.sin() * azimuth.cos(),
+            polar.cos(),
+            polar.sin() * azimuth.sin(),
+        )
+    }
+
+    /// The same distance `transform::great_circle_distance` takes between sphere coords,
+    /// worked out here on unit vectors so the benchmarks can still include this file.
+    fn great_circle_distance(a: Vec3, b: Vec3) -> f32 {
+        a.dot(b).clamp(-1.0, 1.0).acos()
+    }
+
+    /// Random points all over the sphere, plus some on either side of the 0/1 longitude
+    /// seam, on the poles and next to them.
+    fn points() -> Vec<Vec3> {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut points: Vec<Vec3> = (0..2000)
+            .map(|_| {
+                let polar = rng.gen_range(-1.0f32..1.0).acos();
+                point(polar, rng.gen_range(0.0..2.0 * PI))
+            })
+            .collect();
+        for polar in [0.3, 1.0, PI / 2.0, 2.5] {
+            for azimuth in [0.0, 0.001, 0.05, 2.0 * PI - 0.001, 2.0 * PI - 0.05] {
+                points.push(point(polar, azimuth));
+            }
+        }
+        for azimuth in [0.0, 1.0, 3.0, 5.0] {
+            for polar in [0.001, 0.05, PI - 0.05, PI - 0.001] {
+                points.push(point(polar, azimuth));
+            }
+        }
+        points.extend([Vec3::Y, -Vec3::Y]);
+        points
+    }
+
+    /// Query points on the seam, at and next to the poles, and a few anywhere else.
+    fn queries() -> Vec<Vec3> {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut queries = vec![
+            Vec3::Y,
+            -Vec3::Y,
+            point(0.01, 2.0),
+            point(PI - 0.01, 4.0),
+            point(0.15, 0.0),
+            point(PI / 2.0, 0.0),
+            point(PI / 2.0, 2.0 * PI - 0.001),
+            point(1.0, 0.001),
+            point(2.8, 2.0 * PI - 0.02),
+        ];
+        queries.extend((0..50).map(|_| {
+            let polar = rng.gen_range(-1.0f32..1.0).acos();
+            point(polar, rng.gen_range(0.0..2.0 * PI))
+        }));
+        queries
+    }
+
+    fn build(points: &[Vec3]) -> SpatialIndex<usize> {
+        let mut index = SpatialIndex::new(BANDS);
+        for (i, point) in points.iter().enumerate() {
+            index.insert(*point, i);
+        }
+        index
+    }
+
+    /// Every point within `radius` of `query`, by the same cutoff the index uses.
+    fn scan(points: &[Vec3], query: Vec3, radius: f32) -> Vec<usize> {
+        let min_dot = radius.min(PI).cos();
+        (0..points.len())
+            .filter(|i| points[*i].normalize().dot(query) >= min_dot)
+            .collect()
+    }
+
+    #[test]
+    fn within_matches_scan() {
+        let points = points();
+        let index = build(&points);
+        assert_eq!(index.len(), points.len());
+
+        for query in queries() {
+            for radius in RADII {
+                let mut found: Vec<usize> = index
+                    .within(query, radius)
+                    .into_iter()
+                    .map(|(i, _)| *i)
+                    .collect();
+                found.sort_unstable();
+                assert_eq!(found, scan(&points, query, radius), "{} {}", query, radius);
+            }
+        }
+    }
+
+    #[test]
+    fn for_each_within_reports_positions_and_distances() {
+        let points = points();
+        let index = build(&points);
+
+        for query in queries() {
+            let mut found = Vec::new();
+            index.for_each_within(query, 0.4, |i, position, distance| {
+                assert!(position.abs_diff_eq(points[*i].normalize(), 1e-6));
+                let expected = great_circle_distance(points[*i], query);
+                // acos loses precision next to the query point
+                assert!(
+                    (distance - expected).abs() < 1e-3,
+                    "{} {}",
+                    distance,
+                    expected
+                );
+                assert!(distance <= 0.4 + 1e-3);
+                found.push(*i);
+            });
+            found.sort_unstable();
+            assert_eq!(found, scan(&points, query, 0.4));
+        }
+    }
+
+    #[test]
+    fn within_crosses_the_seam() {
+        let before = point(PI / 2.0, 2.0 * PI - 0.01);
+        let after = point(PI / 2.0, 0.01);
+        let index = build(&[before, after]);
+
+        for query in [before, after, point(PI / 2.0, 0.0)] {
+            assert_eq!(index.within(query, 0.05).len(), 2, "{}", query);
+        }
+    }
+
+    #[test]
+    fn within_reaches_over_the_poles() {
+        // on opposite sides of each pole, so a cap around one reaches the other
+        let points = [
+            point(0.05, 0.0),
+            point(0.05, PI),
+            point(PI - 0.05, 1.0),
+            point(PI - 0.05, 1.0 + PI),
+        ];
+        let index = build(&points);
+
+        assert_eq!(index.within(points[0], 0.11).len(), 2);
+        assert_eq!(index.within(points[2], 0.11).len(), 2);
+        assert_eq!(index.within(Vec3::Y, 0.06).len(), 2);
+        assert_eq!(index.within(-Vec3::Y, 0.06).len(), 2);
+    }
+
+    #[test]
+    fn nearest_matches_scan() {
+        let points = points();
+        let index = build(&points);
+
+        for query in queries() {
+            for max_radius in RADII {
+                // a sparse filter, so the search has to grow its cap to find anything
+                let filter = |i: &usize| i % 97 == 0;
+                let expected = (0..points.len())
+                    .filter(filter)
+                    .map(|i| (i, great_circle_distance(points[i], query)))
+                    .filter(|(_, distance)| *distance <= max_radius)
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+
+                let found = index.nearest(query, max_radius, filter);
+                match (found, expected) {
+                    (None, None) => {}
+                    (Some((_, distance)), Some((_, expected))) => {
+                        assert!(
+                            (distance - expected).abs() < 1e-4,
+                            "{} {}",
+                            query,
+                            max_radius
+                        );
+                    }
+                    (found, expected) => {
+                        panic!(
+                            "{} {}: {:?} instead of {:?}",
+                            query, max_radius, found, expected
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_grows_its_cap_until_a_hit() {
+        let far = point(PI / 2.0, 2.0);
+        let index = build(&[far]);
+        let query = point(PI / 2.0, 0.0);
+
+        let (item, distance) = index.nearest(query, PI, |_| true).unwrap();
+        assert_eq!(*item, 0);
+        assert!((distance - 2.0).abs() < 1e-4);
+        assert!(index.nearest(query, 1.9, |_| true).is_none());
+        assert!(index.nearest(query, PI, |_| false).is_none());
+    }
+
+    #[test]
+    fn within_cone_matches_scan() {
+        let points = points();
+        let index = build(&points);
+        let half_angle = 0.6;
+
+        for query in queries() {
+            // any tangent will do, as long as it isn't parallel to the axis at the poles
+            let axis = if query.y.abs() > 0.9 {
+                Vec3::X
+            } else {
+                Vec3::Y
+            };
+            let facing = query.cross(axis);
+            let query = query.normalize();
+            let tangent = (facing - query * facing.dot(query)).normalize();
+            for radius in RADII {
+                let mut found: Vec<usize> = index
+                    .within_cone(query, facing, half_angle, radius)
+                    .into_iter()
+                    .map(|(i, _)| *i)
+                    .collect();
+                found.sort_unstable();
+
+                let expected: Vec<usize> = scan(&points, query, radius)
+                    .into_iter()
+                    .filter(|i| {
+                        let position = points[*i].normalize();
+                        let direction =
+                            (position - query * position.dot(query)).normalize_or_zero();
+                        direction == Vec3::ZERO || direction.dot(tangent) >= half_angle.cos()
+                    })
+                    .collect();
+                assert_eq!(found, expected, "{} {}", query, radius);
+            }
+        }
+    }
+}
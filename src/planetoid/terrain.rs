@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::state::WorldParams;
+
+use super::transform::normalized_sphere_to_cartesian;
+
+/// How far the terrain noise displaces the surface per unit of amplitude.
+const DISPLACEMENT_SCALE: f32 = 0.02;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Biome {
+    Tundra,
+    Highland,
+    Desert,
+    Forest,
+    Grassland,
+}
+
+impl Biome {
+    /// Typical temperature of the biome, on the same scale as `temperature`.
+    pub(crate) fn temperature(self) -> f32 {
+        match self {
+            Biome::Tundra => 0.1,
+            Biome::Highland => 0.35,
+            Biome::Forest => 0.5,
+            Biome::Grassland => 0.6,
+            Biome::Desert => 0.9,
+        }
+    }
+}
+
+fn rand2(n: Vec2) -> f32 {
+    let value = n.dot(Vec2::new(12.9898, 4.1414)).sin() * 43758.5453;
+    // WGSL's fract, which unlike f32::fract is never negative
+    value - value.floor()
+}
+
+fn noise2(n: Vec2) -> f32 {
+    let b = n.floor();
+    let f = n - b;
+    let f = f * f * (Vec2::splat(3.0) - 2.0 * f);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    lerp(
+        lerp(rand2(b), rand2(b + Vec2::X), f.x),
+        lerp(rand2(b + Vec2::Y), rand2(b + Vec2::ONE), f.x),
+        f.y,
+    )
+}
+
+/// The polar coordinates the planetoid shader feeds its terrain noise with.
+fn shader_polar(sphere_coords: Vec2) -> Vec2 {
+    let p = normalized_sphere_to_cartesian(sphere_coords);
+    Vec2::new(p.z.atan2(p.x), (p.x * p.x + p.y * p.y).sqrt().atan2(p.z))
+}
+
+/// Terrain noise in `-1.0..=1.0`, the same the vertex shader displaces the surface by.
+pub(crate) fn terrain_noise(sphere_coords: Vec2, params: &WorldParams) -> f32 {
+    noise2(shader_polar(sphere_coords) * 2.0 + params.terrain_offset()) * 2.0 - 1.0
+}
+
+/// Radial displacement of the surface from the unit sphere.
+pub(crate) fn surface_height(sphere_coords: Vec2, params: &WorldParams) -> f32 {
+    terrain_noise(sphere_coords, params) * params.terrain_amplitude * DISPLACEMENT_SCALE
+}
+
+/// 1.0 at the equator, 0.0 at the poles, a bit colder the higher up.
+pub(crate) fn temperature(sphere_coords: Vec2, params: &WorldParams) -> f32 {
+    let latitude = (sphere_coords.y * 2.0 - 1.0).abs();
+    ((1.0 - latitude) - terrain_noise(sphere_coords, params).max(0.0) * 0.3).clamp(0.0, 1.0)
+}
+
+pub(crate) fn biome_at(sphere_coords: Vec2, params: &WorldParams) -> Biome {
+    let height = terrain_noise(sphere_coords, params);
+    let temperature = temperature(sphere_coords, params);
+    let moisture = noise2(shader_polar(sphere_coords) * 3.0 - params.terrain_offset());
+
+    if temperature < 0.25 {
+        Biome::Tundra
+    } else if height > 0.6 {
+        Biome::Highland
+    } else if temperature > 0.7 && moisture < 0.5 {
+        Biome::Desert
+    } else if moisture > 0.55 {
+        Biome::Forest
+    } else {
+        Biome::Grassland
+    }
+}
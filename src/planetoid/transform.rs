@@ -1,28 +1,139 @@
-use std::f32::consts::PI;
+use std::{collections::HashMap, f32::consts::PI};
 
 use bevy::prelude::*;
 
-use super::PlanetoidRotation;
+use super::{heightfield::Heightfield, spatial::SpatialIndex, Planetoid};
 
-#[derive(Component, Default)]
+#[derive(Component)]
 pub(crate) struct PlanetoidTransform {
     pub(crate) sphere_coords: Vec2,
     pub(crate) rotation: f32,
+    pub(crate) scale: f32,
 }
 
+impl Default for PlanetoidTransform {
+    fn default() -> Self {
+        Self {
+            sphere_coords: Vec2::ZERO,
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Latitude bands of the surface index, about a tenth of a radian tall each.
+const SURFACE_INDEX_BANDS: usize = 32;
+
+/// Every entity with a `PlanetoidTransform`, bucketed by the planetoid it's on and by
+/// position for proximity queries. Rebuilt at the start of every frame, so entities spawned
+/// or moved during the frame only show up in the next one.
+#[derive(Default)]
+pub(crate) struct SurfaceIndex(HashMap<Entity, SpatialIndex<Entity>>);
+
+impl SurfaceIndex {
+    /// Entities on the planetoid at most `radius` radians from the given point, with their
+    /// distance.
+    pub(crate) fn within(
+        &self,
+        planetoid: Entity,
+        sphere_coords: Vec2,
+        radius: f32,
+    ) -> Vec<(Entity, f32)> {
+        let index = match self.0.get(&planetoid) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        index
+            .within(normalized_sphere_to_cartesian(sphere_coords), radius)
+            .into_iter()
+            .map(|(entity, distance)| (*entity, distance))
+            .collect()
+    }
+
+    /// The nearest entity on the planetoid passing `filter`, at most `max_radius` radians
+    /// away.
+    pub(crate) fn nearest(
+        &self,
+        planetoid: Entity,
+        sphere_coords: Vec2,
+        max_radius: f32,
+        filter: impl FnMut(&Entity) -> bool,
+    ) -> Option<(Entity, f32)> {
+        self.0
+            .get(&planetoid)?
+            .nearest(normalized_sphere_to_cartesian(sphere_coords), max_radius, filter)
+            .map(|(entity, distance)| (*entity, distance))
+    }
+
+    /// Entities on the planetoid at most `radius` radians away in the sector of
+    /// `half_angle` radians around the direction towards `facing`.
+    pub(crate) fn within_cone(
+        &self,
+        planetoid: Entity,
+        sphere_coords: Vec2,
+        facing: Vec2,
+        half_angle: f32,
+        radius: f32,
+    ) -> Vec<(Entity, f32)> {
+        let index = match self.0.get(&planetoid) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        let position = normalized_sphere_to_cartesian(sphere_coords);
+        index
+            .within_cone(
+                position,
+                normalized_sphere_to_cartesian(facing) - position,
+                half_angle,
+                radius,
+            )
+            .into_iter()
+            .map(|(entity, distance)| (*entity, distance))
+            .collect()
+    }
+}
+
+pub(crate) fn rebuild_surface_index(
+    mut index: ResMut<SurfaceIndex>,
+    query: Query<(Entity, &PlanetoidTransform, &Parent)>,
+    planetoids: Query<(), With<Planetoid>>,
+) {
+    // keep the buckets of planetoids still around, rather than allocating them every frame
+    index.0.retain(|planetoid, planetoid_index| {
+        planetoid_index.clear();
+        planetoids.get(*planetoid).is_ok()
+    });
+    for (entity, transform, parent) in query.iter() {
+        index
+            .0
+            .entry(parent.get())
+            .or_insert_with(|| SpatialIndex::new(SURFACE_INDEX_BANDS))
+            .insert(
+                normalized_sphere_to_cartesian(transform.sphere_coords),
+                entity,
+            );
+    }
+}
+
+/// Places everything on a planetoid, which they are children of, relative to it.
 pub(crate) fn match_planetoid_transforms(
-    planetoid_rotation: Res<PlanetoidRotation>,
-    mut query: Query<(&mut Transform, &PlanetoidTransform)>,
+    heightfields: Query<&Heightfield, With<Planetoid>>,
+    mut query: Query<(&mut Transform, &PlanetoidTransform, Option<&Parent>)>,
 ) {
-    for (mut transform, planetoid_transform) in query.iter_mut() {
-        let matrix = Mat4::from_quat(planetoid_rotation.0)
-            * Mat4::from_axis_angle(
-                Quat::from_rotation_y(-(planetoid_transform.sphere_coords.x - 0.5) * PI * 2.0 + PI)
-                    * Vec3::new(0.0, 0.0, 1.0),
-                planetoid_transform.sphere_coords.y * PI,
-            )
-            * Mat4::from_translation(Vec3::new(0.0, 1.0, 0.0))
-            * Mat4::from_rotation_y(-planetoid_transform.rotation);
+    for (mut transform, planetoid_transform, parent) in query.iter_mut() {
+        // stand on the terrain rather than the unit sphere
+        let heightfield = parent.and_then(|parent| heightfields.get(parent.get()).ok());
+        let radius = 1.0
+            + heightfield.map_or(0.0, |heightfield| {
+                heightfield.height_at(planetoid_transform.sphere_coords)
+            });
+        let matrix = Mat4::from_axis_angle(
+            Quat::from_rotation_y(-(planetoid_transform.sphere_coords.x - 0.5) * PI * 2.0 + PI)
+                * Vec3::new(0.0, 0.0, 1.0),
+            planetoid_transform.sphere_coords.y * PI,
+        ) * Mat4::from_translation(Vec3::new(0.0, radius, 0.0))
+            * Mat4::from_rotation_y(-planetoid_transform.rotation)
+            * Mat4::from_scale(Vec3::splat(planetoid_transform.scale));
 
         *transform = Transform::from_matrix(matrix);
     }
@@ -35,9 +146,51 @@ pub(crate) fn cartesian_to_normalized_sphere(pos: Vec3) -> Vec2 {
     )
 }
 
+/// Inverse of `cartesian_to_normalized_sphere`, giving a point on the unit sphere.
 pub(crate) fn normalized_sphere_to_cartesian(sphere_coords: Vec2) -> Vec3 {
-    let x = f32::sin(sphere_coords.y * PI) * f32::cos(sphere_coords.x * PI);
+    let x = -f32::sin(sphere_coords.y * PI) * f32::cos(sphere_coords.x * PI * 2.0);
     let y = f32::cos(sphere_coords.y * PI);
-    let z = f32::sin(sphere_coords.y * PI) * f32::sin(sphere_coords.x * PI);
+    let z = -f32::sin(sphere_coords.y * PI) * f32::sin(sphere_coords.x * PI * 2.0);
     Vec3::new(x, y, z)
 }
+
+/// Angle in radians between two points on the planetoid surface.
+pub(crate) fn great_circle_distance(a: Vec2, b: Vec2) -> f32 {
+    let a = normalized_sphere_to_cartesian(a);
+    let b = normalized_sphere_to_cartesian(b);
+    a.dot(b).clamp(-1.0, 1.0).acos()
+}
+
+/// Moves `from` along the great circle towards `to` by at most `angle` radians.
+pub(crate) fn move_towards(from: Vec2, to: Vec2, angle: f32) -> Vec2 {
+    let a = normalized_sphere_to_cartesian(from);
+    let b = normalized_sphere_to_cartesian(to);
+    let axis = a.cross(b);
+
+    if a.dot(b).clamp(-1.0, 1.0).acos() <= angle {
+        return to;
+    }
+    // exactly opposite points have no single shortest path, so just wait
+    if axis.length_squared() < 1e-8 {
+        return from;
+    }
+
+    cartesian_to_normalized_sphere(Quat::from_axis_angle(axis.normalize(), angle) * a)
+}
+
+/// Unit vector along the surface at `from` pointing towards `to`, both on the unit sphere.
+/// Zero when the points coincide or are exactly opposite.
+pub(crate) fn tangent_towards(from: Vec3, to: Vec3) -> Vec3 {
+    (to - from * to.dot(from)).normalize_or_zero()
+}
+
+/// Moves `from` by `angle` radians along the great circle in the direction of `tangent`.
+pub(crate) fn move_along(from: Vec2, tangent: Vec3, angle: f32) -> Vec2 {
+    let position = normalized_sphere_to_cartesian(from);
+    let axis = position.cross(tangent);
+    if axis.length_squared() < 1e-8 {
+        return from;
+    }
+
+    cartesian_to_normalized_sphere(Quat::from_axis_angle(axis.normalize(), angle) * position)
+}
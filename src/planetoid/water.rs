@@ -0,0 +1,330 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    f32::consts::PI,
+};
+
+use bevy::{pbr::NotShadowCaster, prelude::*};
+
+use crate::{
+    clock::DAY_LENGTH,
+    state::{InGame, WorldParams},
+    GameWorldRenderLayer,
+};
+
+use super::{
+    grid::SurfaceGrid,
+    heightfield::Heightfield,
+    terrain::{biome_at, Biome},
+    transform::great_circle_distance,
+    Planetoid,
+};
+
+/// Sea level per unit of terrain amplitude, putting roughly a third of the surface under water.
+const SEA_LEVEL_PER_AMPLITUDE: f32 = -0.006;
+/// Seconds of game time between hydrology steps.
+const HYDROLOGY_STEP: f32 = 0.2;
+/// Depth of rain per day where it rains the most.
+const RAIN_PER_DAY: f32 = 0.002;
+/// Depth of water that evaporates per day, plus a fraction of the standing water.
+const EVAPORATION_PER_DAY: f32 = 0.0015;
+const EVAPORATION_FRACTION_PER_DAY: f32 = 0.5;
+/// Fraction of the height difference to its lower neighbors a cell drains per step.
+const FLOW_RATE: f32 = 0.5;
+/// How quickly the tracked flow of a cell follows what actually runs through it.
+const FLOW_SMOOTHING: f32 = 0.1;
+/// Cells with more water running through them per day than this are rivers.
+const RIVER_FLOW: f32 = RAIN_PER_DAY * 4.0;
+/// How deep a river looks, as rivers themselves barely hold any water.
+const RIVER_DEPTH: f32 = 0.002;
+/// Standing water shallower than this is just wet ground.
+const MIN_VISIBLE_DEPTH: f32 = 0.001;
+/// Where the water shell hides under dry ground.
+const DRY_OFFSET: f32 = 0.02;
+
+/// Water on a planetoid: a global sea, plus rain running off into lakes and rivers on a grid
+/// matching the planetoid's `Heightfield`.
+#[derive(Component)]
+pub(crate) struct Water {
+    /// Current level of the sea, which the tides move around `mean_sea_level`.
+    pub(crate) sea_level: f32,
+    mean_sea_level: f32,
+    /// Scales the rain everywhere, so weather can hold it back.
+    pub(crate) rain_scale: f32,
+    /// Standing water above the ground, outside the sea.
+    depth: SurfaceGrid<f32>,
+    /// Water running through each cell per day, smoothed over time.
+    flow: SurfaceGrid<f32>,
+    /// Fraction of `RAIN_PER_DAY` each cell gets.
+    rainfall: SurfaceGrid<f32>,
+    /// Seconds of game time not yet simulated.
+    pending: f32,
+}
+
+/// Renders the water of the planetoid it's a child of.
+#[derive(Component)]
+pub(crate) struct WaterShell {
+    surface: Heightfield,
+}
+
+fn biome_rainfall(biome: Biome) -> f32 {
+    match biome {
+        Biome::Forest | Biome::Highland => 1.0,
+        Biome::Grassland => 0.7,
+        Biome::Tundra => 0.5,
+        Biome::Desert => 0.1,
+    }
+}
+
+impl Water {
+    pub(crate) fn new(ground: &Heightfield, params: &WorldParams) -> Self {
+        let (width, height) = (ground.heights().width(), ground.heights().height());
+        let sea_level = params.terrain_amplitude * SEA_LEVEL_PER_AMPLITUDE;
+        Self {
+            sea_level,
+            mean_sea_level: sea_level,
+            rain_scale: 1.0,
+            depth: SurfaceGrid::new(width, height, 0.0),
+            flow: SurfaceGrid::new(width, height, 0.0),
+            rainfall: SurfaceGrid::from_fn(width, height, |sphere_coords| {
+                biome_rainfall(biome_at(sphere_coords, params))
+            }),
+            pending: 0.0,
+        }
+    }
+
+    /// Raises or lowers the sea by `tide` from its mean level.
+    pub(crate) fn set_tide(&mut self, tide: f32) {
+        self.sea_level = self.mean_sea_level + tide;
+    }
+
+    fn is_sea(&self, ground: &Heightfield, index: usize) -> bool {
+        ground.heights().cells()[index] < self.sea_level
+    }
+
+    /// Depth of the water at a point, the sea included.
+    pub(crate) fn depth_at(&self, ground: &Heightfield, sphere_coords: Vec2) -> f32 {
+        let index = self.depth.index(sphere_coords);
+        let sea = (self.sea_level - ground.height_at(sphere_coords)).max(0.0);
+        sea + self.depth.cells()[index]
+    }
+
+    /// Whether there is water to drink at the given cell.
+    fn has_water(&self, ground: &Heightfield, index: usize) -> bool {
+        self.is_sea(ground, index)
+            || self.depth.cells()[index] >= MIN_VISIBLE_DEPTH
+            || self.flow.cells()[index] >= RIVER_FLOW
+    }
+
+    /// Whether there is water to drink at or right next to the given point.
+    pub(crate) fn can_drink_at(&self, ground: &Heightfield, sphere_coords: Vec2) -> bool {
+        let index = self.depth.index(sphere_coords);
+        self.has_water(ground, index)
+            || self
+                .depth
+                .neighbors(index)
+                .any(|neighbor| self.has_water(ground, neighbor))
+    }
+
+    /// The nearest point with water to drink at most `max_radius` radians away, only looking
+    /// at the cells spreading out from the point up to that distance.
+    pub(crate) fn nearest_water(
+        &self,
+        ground: &Heightfield,
+        sphere_coords: Vec2,
+        max_radius: f32,
+    ) -> Option<Vec2> {
+        // a cell is visited while any part of it could be in reach, which is as far as its
+        // center can be from its corners at the equator
+        let (width, height) = (self.depth.width() as f32, self.depth.height() as f32);
+        let reach = max_radius + (PI / width).hypot(PI / (2.0 * height));
+
+        let start = self.depth.index(sphere_coords);
+        let mut visited = HashSet::from([start]);
+        let mut frontier = VecDeque::from([(
+            start,
+            great_circle_distance(sphere_coords, self.depth.center(start)),
+        )]);
+        let mut nearest: Option<(f32, usize)> = None;
+        while let Some((index, distance)) = frontier.pop_front() {
+            if distance <= max_radius
+                && nearest.map_or(true, |(closest, _)| distance < closest)
+                && self.has_water(ground, index)
+            {
+                nearest = Some((distance, index));
+            }
+
+            for neighbor in self.depth.neighbors(index) {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let distance = great_circle_distance(sphere_coords, self.depth.center(neighbor));
+                if distance <= reach {
+                    frontier.push_back((neighbor, distance));
+                }
+            }
+        }
+        nearest.map(|(_, index)| self.depth.center(index))
+    }
+
+    /// How wet the ground is at a point, from 0.0 bone dry to 1.0 at a lake or river.
+    pub(crate) fn moisture_at(&self, ground: &Heightfield, sphere_coords: Vec2) -> f32 {
+        let index = self.depth.index(sphere_coords);
+        let wet = |index: usize| {
+            if self.has_water(ground, index) {
+                1.0
+            } else {
+                (self.flow.cells()[index] / RIVER_FLOW).min(1.0)
+            }
+        };
+        let neighbors = self.depth.neighbors(index).map(wet).fold(0.0, f32::max);
+        wet(index).max(neighbors * 0.5)
+    }
+
+    /// Advances rain, evaporation and runoff by one hydrology step.
+    fn step(&mut self, ground: &Heightfield) {
+        let days = HYDROLOGY_STEP / DAY_LENGTH;
+        let heights = ground.heights().cells();
+        let cells = heights.len();
+
+        for index in 0..cells {
+            let depth = &mut self.depth.cells_mut()[index];
+            *depth += RAIN_PER_DAY * self.rainfall.cells()[index] * self.rain_scale * days;
+            *depth -= (EVAPORATION_PER_DAY + *depth * EVAPORATION_FRACTION_PER_DAY) * days;
+            *depth = depth.max(0.0);
+        }
+
+        // water runs off towards lower neighbors in proportion to how much lower they are,
+        // and whatever reaches the sea is gone
+        let mut change = vec![0.0; cells];
+        let mut outflow = vec![0.0; cells];
+        for index in 0..cells {
+            let depth = self.depth.cells()[index];
+            if depth <= 0.0 || self.is_sea(ground, index) {
+                continue;
+            }
+
+            let level = heights[index] + depth;
+            let drops: Vec<(usize, f32)> = self
+                .depth
+                .neighbors(index)
+                .map(|neighbor| {
+                    let neighbor_level = heights[neighbor] + self.depth.cells()[neighbor];
+                    (neighbor, (level - neighbor_level).max(0.0))
+                })
+                .filter(|(_, drop)| *drop > 0.0)
+                .collect();
+            let total_drop: f32 = drops.iter().map(|(_, drop)| drop).sum();
+            if total_drop <= 0.0 {
+                continue;
+            }
+
+            // never drain below the neighbors, so lakes settle level instead of sloshing
+            let amount = depth.min(total_drop / 2.0) * FLOW_RATE;
+            change[index] -= amount;
+            outflow[index] = amount;
+            for (neighbor, drop) in drops {
+                if !self.is_sea(ground, neighbor) {
+                    change[neighbor] += amount * drop / total_drop;
+                }
+            }
+        }
+
+        for index in 0..cells {
+            let depth = &mut self.depth.cells_mut()[index];
+            *depth = (*depth + change[index]).max(0.0);
+            let flow = &mut self.flow.cells_mut()[index];
+            *flow += (outflow[index] / days - *flow) * FLOW_SMOOTHING;
+        }
+    }
+
+    /// Height of the visible water surface of a cell, or somewhere under the ground if dry.
+    fn shell_height(&self, ground: &Heightfield, index: usize) -> f32 {
+        let ground_height = ground.heights().cells()[index];
+        let depth = self.depth.cells()[index];
+        if self.is_sea(ground, index) {
+            self.sea_level
+        } else if depth >= MIN_VISIBLE_DEPTH {
+            ground_height + depth
+        } else if self.flow.cells()[index] >= RIVER_FLOW {
+            ground_height + RIVER_DEPTH
+        } else {
+            ground_height - DRY_OFFSET
+        }
+    }
+}
+
+pub(crate) fn spawn_water_shell(
+    commands: &mut Commands,
+    planetoid: Entity,
+    water: &Water,
+    ground: &Heightfield,
+    game_world_render_layer: &GameWorldRenderLayer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    let heights = ground.heights();
+    let surface = Heightfield::from_heights(SurfaceGrid::from_fn(
+        heights.width(),
+        heights.height(),
+        |sphere_coords| water.shell_height(ground, heights.index(sphere_coords)),
+    ));
+
+    let shell = commands
+        .spawn_bundle(MaterialMeshBundle {
+            mesh: meshes.add(surface.build_mesh()),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(0.16, 0.42, 0.9, 0.85),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            ..default()
+        })
+        // the sea is see-through, and the dry parts of the shell hide under the ground
+        .insert(NotShadowCaster)
+        .insert(WaterShell { surface })
+        .insert(InGame)
+        .insert(game_world_render_layer.0)
+        .id();
+    commands.entity(planetoid).add_child(shell);
+}
+
+pub(crate) fn simulate_water(
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut planetoids: Query<(&mut Water, &Heightfield, &Children), With<Planetoid>>,
+    mut shells: Query<(&mut WaterShell, &Handle<Mesh>)>,
+) {
+    for (mut water, ground, children) in planetoids.iter_mut() {
+        // after a long hitch, catch up a few steps rather than stalling on all of them
+        water.pending = (water.pending + time.delta_seconds()).min(HYDROLOGY_STEP * 5.0);
+        if water.pending < HYDROLOGY_STEP {
+            continue;
+        }
+        while water.pending >= HYDROLOGY_STEP {
+            water.pending -= HYDROLOGY_STEP;
+            water.step(ground);
+        }
+
+        for child in children.iter() {
+            let (mut shell, mesh) = match shells.get_mut(*child) {
+                Ok(shell) => shell,
+                Err(_) => continue,
+            };
+
+            // only touch the parts of the mesh where the water actually moved
+            let changes: Vec<(usize, f32)> = (0..ground.heights().cells().len())
+                .map(|index| (index, water.shell_height(ground, index)))
+                .filter(|(index, height)| {
+                    (shell.surface.heights().cells()[*index] - height).abs() > 1e-4
+                })
+                .collect();
+            if changes.is_empty() {
+                continue;
+            }
+            if let Some(mesh) = meshes.get_mut(mesh) {
+                shell.surface.set_heights(mesh, &changes);
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    clock::{GameClock, DAY_LENGTH},
+    ecosystem::Soil,
+    planetoid::{
+        heightfield::Heightfield,
+        in_daylight,
+        terrain::{biome_at, Biome},
+        transform::{move_towards, PlanetoidTransform},
+        water::Water,
+        Planetoid,
+    },
+    state::{AppState, InGame, WorldRng},
+    GameWorldRenderLayer,
+};
+
+const STARTING_PLANTS: usize = 40;
+/// Growth per day in perfectly fertile soil.
+const GROWTH_RATE: f32 = 0.5;
+/// Fertility used up per unit of growth.
+const FERTILITY_USE: f32 = 0.1;
+/// Chance per day that a mature plant drops a seed.
+const SEED_CHANCE_PER_DAY: f32 = 0.6;
+/// Farthest a dropped seed lands from its parent, in radians.
+const SEED_SPREAD: f32 = 0.15;
+/// Plants under more water than this drown.
+const DROWN_DEPTH: f32 = 0.004;
+/// Seeds don't take root in soil poorer than this.
+const MIN_SEED_FERTILITY: f32 = 0.2;
+
+pub(crate) struct PlantPlugin;
+
+impl Plugin for PlantPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlantCap>()
+            .add_event::<SpawnPlant>()
+            .add_event::<PlantMatured>()
+            .add_event::<PlantDied>()
+            .add_startup_system(setup_plant_materials)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(setup_plants)
+                    .with_system(spawn_plants.after(setup_plants))
+                    .with_system(grow_plants)
+                    .with_system(spread_seeds)
+                    .with_system(wither_plants)
+                    .with_system(update_plant_appearance),
+            );
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct Plant {
+    /// 0.0 is a fresh sprout, 1.0 fully grown. Eating a plant shrinks it back.
+    pub(crate) growth: f32,
+    /// How well the plant is doing in its soil, from 0.0 to 1.0.
+    pub(crate) health: f32,
+    pub(crate) matured: bool,
+}
+
+pub(crate) struct PlantCap(pub(crate) usize);
+
+impl Default for PlantCap {
+    fn default() -> Self {
+        Self(120)
+    }
+}
+
+pub(crate) struct SpawnPlant {
+    pub(crate) planetoid: Entity,
+    pub(crate) sphere_coords: Vec2,
+}
+
+pub(crate) struct PlantMatured {
+    pub(crate) entity: Entity,
+    pub(crate) biome: Biome,
+}
+
+pub(crate) struct PlantDied {
+    pub(crate) planetoid: Entity,
+    pub(crate) sphere_coords: Vec2,
+}
+
+struct PlantMaterials {
+    healthy: Handle<StandardMaterial>,
+    /// Healthy plants glow on the night side.
+    glowing: Handle<StandardMaterial>,
+    weak: Handle<StandardMaterial>,
+    withered: Handle<StandardMaterial>,
+}
+
+fn setup_plant_materials(mut commands: Commands, mut materials: ResMut<Assets<StandardMaterial>>) {
+    let mut flat = |color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            unlit: true,
+            ..default()
+        })
+    };
+
+    commands.insert_resource(PlantMaterials {
+        healthy: flat(Color::rgb(0.2, 0.8, 0.3)),
+        glowing: flat(Color::rgb(0.16, 0.68, 1.0)),
+        weak: flat(Color::rgb(0.7, 0.7, 0.2)),
+        withered: flat(Color::rgb(0.5, 0.35, 0.2)),
+    });
+}
+
+fn setup_plants(
+    mut spawn_events: EventWriter<SpawnPlant>,
+    planetoids: Query<(Entity, &Planetoid), Added<Planetoid>>,
+) {
+    for (entity, planetoid) in planetoids.iter() {
+        // not the world rng, so the plants only depend on the planetoid they grow on
+        let mut rng = SmallRng::seed_from_u64(planetoid.params.seed);
+        for _ in 0..STARTING_PLANTS {
+            spawn_events.send(SpawnPlant {
+                planetoid: entity,
+                sphere_coords: Vec2::new(rng.gen_range(0.0..1.0), rng.gen_range(0.1..0.9)),
+            });
+        }
+    }
+}
+
+fn spawn_plants(
+    mut commands: Commands,
+    mut spawn_events: EventReader<SpawnPlant>,
+    mut rng: ResMut<WorldRng>,
+    cap: Res<PlantCap>,
+    plant_materials: Res<PlantMaterials>,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    asset_server: Res<AssetServer>,
+    plants: Query<(), With<Plant>>,
+) {
+    let mut population = plants.iter().count();
+
+    for event in spawn_events.iter() {
+        if population >= cap.0 {
+            break;
+        }
+        population += 1;
+
+        let plant = commands
+            .spawn_bundle(MaterialMeshBundle {
+                mesh: asset_server.load("models/tringle.glb#Mesh0/Primitive0"),
+                material: plant_materials.healthy.clone(),
+                ..default()
+            })
+            .insert(PlanetoidTransform {
+                sphere_coords: event.sphere_coords,
+                rotation: rng.0.gen_range(0.0..PI * 2.0),
+                scale: 0.0,
+            })
+            .insert(Plant {
+                growth: 0.1,
+                health: 1.0,
+                matured: false,
+            })
+            .insert(InGame)
+            .insert(game_world_render_layer.0)
+            .id();
+        commands.entity(event.planetoid).add_child(plant);
+    }
+}
+
+fn biome_growth(biome: Biome) -> f32 {
+    match biome {
+        Biome::Forest => 1.2,
+        Biome::Grassland => 1.0,
+        Biome::Highland => 0.6,
+        Biome::Tundra => 0.4,
+        Biome::Desert => 0.3,
+    }
+}
+
+fn grow_plants(
+    time: Res<Time>,
+    mut matured_events: EventWriter<PlantMatured>,
+    mut planetoids: Query<(&Planetoid, &mut Soil, &Water, &Heightfield)>,
+    mut plants: Query<(Entity, &mut Plant, &PlanetoidTransform, &Parent)>,
+) {
+    let days = time.delta_seconds() / DAY_LENGTH;
+    for (entity, mut plant, transform, parent) in plants.iter_mut() {
+        let (planetoid, mut soil, water, ground) = match planetoids.get_mut(parent.get()) {
+            Ok(planetoid) => planetoid,
+            Err(_) => continue,
+        };
+        let biome = biome_at(transform.sphere_coords, &planetoid.params);
+        let fertility = soil.fertility.get_mut(transform.sphere_coords);
+
+        let moisture = water.moisture_at(ground, transform.sphere_coords);
+        let drowned = water.depth_at(ground, transform.sphere_coords) > DROWN_DEPTH;
+
+        // health follows how good the ground is, with a bit of lag
+        let quality = if drowned {
+            0.0
+        } else {
+            (*fertility * (1.5 + moisture)).min(1.0)
+        };
+        plant.health += (quality - plant.health) * days.min(1.0);
+
+        let growth = (GROWTH_RATE * *fertility * (0.5 + moisture) * biome_growth(biome) * days)
+            .min(1.0 - plant.growth)
+            .max(0.0);
+        plant.growth += growth;
+        *fertility = (*fertility - growth * FERTILITY_USE).max(0.0);
+
+        if plant.growth >= 1.0 && !plant.matured {
+            plant.matured = true;
+            matured_events.send(PlantMatured { entity, biome });
+        }
+    }
+}
+
+fn spread_seeds(
+    time: Res<Time>,
+    mut rng: ResMut<WorldRng>,
+    mut spawn_events: EventWriter<SpawnPlant>,
+    planetoids: Query<(&Soil, &Water, &Heightfield), With<Planetoid>>,
+    plants: Query<(&Plant, &PlanetoidTransform, &Parent)>,
+) {
+    let chance = (SEED_CHANCE_PER_DAY * time.delta_seconds() / DAY_LENGTH).min(1.0) as f64;
+    for (plant, transform, parent) in plants.iter() {
+        if plant.growth < 1.0 || !rng.0.gen_bool(chance) {
+            continue;
+        }
+        let (soil, water, ground) = match planetoids.get(parent.get()) {
+            Ok(planetoid) => planetoid,
+            Err(_) => continue,
+        };
+
+        let direction = Vec2::new(rng.0.gen_range(-1.0..1.0), rng.0.gen_range(-1.0..1.0));
+        let towards = transform.sphere_coords + direction;
+        let sphere_coords = move_towards(
+            transform.sphere_coords,
+            Vec2::new(towards.x, towards.y.clamp(0.0, 1.0)),
+            rng.0.gen_range(0.02..SEED_SPREAD),
+        );
+
+        if *soil.fertility.get(sphere_coords) >= MIN_SEED_FERTILITY
+            && water.depth_at(ground, sphere_coords) <= DROWN_DEPTH
+        {
+            spawn_events.send(SpawnPlant {
+                planetoid: parent.get(),
+                sphere_coords,
+            });
+        }
+    }
+}
+
+fn wither_plants(
+    mut commands: Commands,
+    mut died_events: EventWriter<PlantDied>,
+    plants: Query<(Entity, &Plant, &PlanetoidTransform, &Parent)>,
+) {
+    for (entity, plant, transform, parent) in plants.iter() {
+        if plant.growth <= 0.0 || plant.health <= 0.05 {
+            commands.entity(entity).despawn_recursive();
+            died_events.send(PlantDied {
+                planetoid: parent.get(),
+                sphere_coords: transform.sphere_coords,
+            });
+        }
+    }
+}
+
+fn update_plant_appearance(
+    clock: Res<GameClock>,
+    plant_materials: Res<PlantMaterials>,
+    planetoids: Query<&Transform, With<Planetoid>>,
+    mut plants: Query<(
+        &Plant,
+        &Parent,
+        &mut PlanetoidTransform,
+        &mut Handle<StandardMaterial>,
+    )>,
+) {
+    let sun = clock.sun_direction();
+    for (plant, parent, mut transform, mut material) in plants.iter_mut() {
+        transform.scale = 0.3 + plant.growth * 0.7;
+
+        let night = planetoids.get(parent.get()).map_or(false, |planetoid| {
+            !in_daylight(planetoid, transform.sphere_coords, sun)
+        });
+        let wanted = if plant.health > 0.6 && night {
+            &plant_materials.glowing
+        } else if plant.health > 0.6 {
+            &plant_materials.healthy
+        } else if plant.health > 0.3 {
+            &plant_materials.weak
+        } else {
+            &plant_materials.withered
+        };
+        if *material != *wanted {
+            *material = wanted.clone();
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+const TUNING_PATH: &str = "postprocess/default.postprocess.ron";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum PostEffect {
+    Dither,
+    Palette,
+    Scanlines,
+    Vignette,
+    Outline,
+    Glow,
+    Highlight,
+    Hud,
+}
+
+impl PostEffect {
+    /// Matches the effect switch in `postprocess_material.wgsl`, 0 being a plain copy.
+    pub(crate) fn shader_id(self) -> f32 {
+        match self {
+            PostEffect::Dither => 1.0,
+            PostEffect::Palette => 2.0,
+            PostEffect::Scanlines => 3.0,
+            PostEffect::Vignette => 4.0,
+            PostEffect::Outline => 5.0,
+            PostEffect::Glow => 6.0,
+            PostEffect::Highlight => 7.0,
+            PostEffect::Hud => 8.0,
+        }
+    }
+
+    fn default_params(self) -> Vec4 {
+        match self {
+            // mode (1 ordered, 2 blue noise), strength, pixels per revolution
+            PostEffect::Dither => Vec4::new(1.0, 0.12, 256.0, 0.0),
+            PostEffect::Palette => Vec4::ZERO,
+            // darkening, line period in pixels
+            PostEffect::Scanlines => Vec4::new(0.25, 2.0, 0.0, 0.0),
+            // strength, inner radius
+            PostEffect::Vignette => Vec4::new(0.6, 0.35, 0.0, 0.0),
+            // luminance threshold, darkening
+            PostEffect::Outline => Vec4::new(0.2, 0.5, 0.0, 0.0),
+            // luminance threshold, intensity, radius in pixels
+            PostEffect::Glow => Vec4::new(0.7, 0.6, 2.0, 0.0),
+            PostEffect::Highlight => Vec4::ZERO,
+            PostEffect::Hud => Vec4::ZERO,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PostEffectSlot {
+    pub(crate) effect: PostEffect,
+    pub(crate) enabled: bool,
+    pub(crate) params: Vec4,
+}
+
+impl PostEffectSlot {
+    pub(crate) fn new(effect: PostEffect, enabled: bool) -> Self {
+        Self {
+            effect,
+            enabled,
+            params: effect.default_params(),
+        }
+    }
+}
+
+/// Post effects applied in order, each pass reading the output of the previous one.
+pub(crate) struct PostProcessChain {
+    pub(crate) slots: Vec<PostEffectSlot>,
+}
+
+impl Default for PostProcessChain {
+    fn default() -> Self {
+        Self {
+            slots: vec![
+                PostEffectSlot::new(PostEffect::Glow, true),
+                PostEffectSlot::new(PostEffect::Outline, false),
+                PostEffectSlot::new(PostEffect::Dither, true),
+                PostEffectSlot::new(PostEffect::Palette, true),
+                PostEffectSlot::new(PostEffect::Highlight, true),
+                PostEffectSlot::new(PostEffect::Hud, true),
+                PostEffectSlot::new(PostEffect::Vignette, false),
+                PostEffectSlot::new(PostEffect::Scanlines, false),
+            ],
+        }
+    }
+}
+
+impl PostProcessChain {
+    pub(crate) fn enabled(&self) -> impl Iterator<Item = &PostEffectSlot> {
+        self.slots.iter().filter(|slot| slot.enabled)
+    }
+
+    /// Parameters of the effect's slot, or zero for passes without an effect.
+    pub(crate) fn params(&self, effect: Option<PostEffect>) -> Vec4 {
+        effect
+            .and_then(|effect| self.slots.iter().find(|slot| slot.effect == effect))
+            .map_or(Vec4::ZERO, |slot| slot.params)
+    }
+
+    pub(crate) fn get_mut(&mut self, effect: PostEffect) -> Option<&mut PostEffectSlot> {
+        self.slots.iter_mut().find(|slot| slot.effect == effect)
+    }
+
+    pub(crate) fn toggle(&mut self, effect: PostEffect) {
+        if let Some(slot) = self.get_mut(effect) {
+            slot.enabled = !slot.enabled;
+        }
+    }
+
+    /// Moves the effect one step earlier in the chain, wrapping around to the end.
+    pub(crate) fn move_earlier(&mut self, effect: PostEffect) {
+        if let Some(index) = self.slots.iter().position(|slot| slot.effect == effect) {
+            let target = if index == 0 {
+                self.slots.len() - 1
+            } else {
+                index - 1
+            };
+            self.slots.swap(index, target);
+        }
+    }
+}
+
+/// Parameters of each effect, loaded from a data file so they can be tuned while the game
+/// runs. Effects left out keep their defaults.
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "5d0c7e42-8a1b-4f63-9e27-b3c4d6f8a015"]
+pub(crate) struct PostEffectTuning {
+    pub(crate) params: HashMap<PostEffect, [f32; 4]>,
+}
+
+#[derive(Default)]
+pub(crate) struct PostEffectTuningLoader;
+
+impl AssetLoader for PostEffectTuningLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let tuning: PostEffectTuning = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(tuning));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["postprocess.ron"]
+    }
+}
+
+pub(crate) struct PostEffectTunings(Handle<PostEffectTuning>);
+
+pub(crate) fn load_tuning(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PostEffectTunings(asset_server.load(TUNING_PATH)));
+}
+
+/// Copies the tuned parameters into the chain whenever the file is loaded or edited.
+pub(crate) fn apply_tuning(
+    mut asset_events: EventReader<AssetEvent<PostEffectTuning>>,
+    tunings: Res<PostEffectTunings>,
+    assets: Res<Assets<PostEffectTuning>>,
+    mut chain: ResMut<PostProcessChain>,
+) {
+    let loaded = asset_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => *handle == tunings.0,
+        _ => false,
+    });
+    let tuning = match assets.get(&tunings.0) {
+        Some(tuning) if loaded => tuning,
+        _ => return,
+    };
+
+    for slot in &mut chain.slots {
+        if let Some(params) = tuning.params.get(&slot.effect) {
+            slot.params = Vec4::from(*params);
+        }
+    }
+}
+
+const PALETTES: [&str; 3] = [
+    "palettes/pico8.png",
+    "palettes/sweetie16.png",
+    "palettes/gameboy.png",
+];
+
+pub(crate) struct PaletteSettings {
+    pub(crate) palettes: Vec<Handle<Image>>,
+    pub(crate) active: usize,
+}
+
+impl PaletteSettings {
+    pub(crate) fn active_palette(&self) -> Option<&Handle<Image>> {
+        self.palettes.get(self.active)
+    }
+}
+
+pub(crate) fn setup_palettes(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(PaletteSettings {
+        palettes: PALETTES.iter().map(|path| assets.load(*path)).collect(),
+        active: 0,
+    });
+}
+
+const EFFECT_KEYS: [(KeyCode, PostEffect); 8] = [
+    (KeyCode::F1, PostEffect::Palette),
+    (KeyCode::F2, PostEffect::Dither),
+    (KeyCode::F3, PostEffect::Scanlines),
+    (KeyCode::F4, PostEffect::Vignette),
+    (KeyCode::F5, PostEffect::Outline),
+    (KeyCode::F6, PostEffect::Glow),
+    (KeyCode::F7, PostEffect::Highlight),
+    (KeyCode::F8, PostEffect::Hud),
+];
+
+pub(crate) fn chain_controls(
+    keys: Res<Input<KeyCode>>,
+    mut chain: ResMut<PostProcessChain>,
+    mut palette_settings: ResMut<PaletteSettings>,
+) {
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+
+    for (key, effect) in EFFECT_KEYS {
+        if keys.just_pressed(key) {
+            if shift {
+                chain.move_earlier(effect);
+            } else {
+                chain.toggle(effect);
+            }
+            bevy::log::info!(
+                "post effects: {:?}",
+                chain.enabled().map(|slot| slot.effect).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    if keys.just_pressed(KeyCode::P) && !palette_settings.palettes.is_empty() {
+        palette_settings.active = (palette_settings.active + 1) % palette_settings.palettes.len();
+    }
+
+    if keys.just_pressed(KeyCode::O) {
+        if let Some(dither) = chain.get_mut(PostEffect::Dither) {
+            // cycle between ordered and blue noise dithering
+            dither.params.x = if dither.params.x > 1.5 { 1.0 } else { 2.0 };
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::{shape::Plane, *},
+    reflect::TypeUuid,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            AsBindGroup, Extent3d, SamplerDescriptor, TextureDescriptor, TextureDimension,
+            TextureFormat, TextureUsages,
+        },
+        texture::ImageSampler,
+        view::RenderLayers,
+    },
+};
+
+use crate::{
+    camera::MainCameraTransform, hud::HudTexture, selection::HighlightMask, WorldRenderTarget,
+};
+
+use self::chain::{
+    apply_tuning, chain_controls, load_tuning, setup_palettes, PaletteSettings, PostEffect,
+    PostEffectTuning, PostEffectTuningLoader,
+};
+
+pub(crate) mod chain;
+
+pub(crate) use self::chain::PostProcessChain;
+
+/// Render layers from here on are reserved for post-processing passes, one per pass.
+const PASS_LAYER_OFFSET: u8 = 10;
+
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PostProcessChain>()
+            .insert_resource(PostProcessPasses {
+                effects: None,
+                entities: Vec::new(),
+                textures: Vec::new(),
+            })
+            .add_plugin(MaterialPlugin::<PostProcessMaterial>::default())
+            .add_asset::<PostEffectTuning>()
+            .init_asset_loader::<PostEffectTuningLoader>()
+            .add_startup_system(setup_palettes)
+            .add_startup_system(load_tuning)
+            .add_system(chain_controls)
+            .add_system(apply_tuning)
+            .add_system(rebuild_passes.after(chain_controls).after(apply_tuning))
+            .add_system(update_postprocess.after(rebuild_passes));
+    }
+}
+
+#[derive(AsBindGroup, TypeUuid, Debug, Clone, Component)]
+#[uuid = "1e55b055-f4c4-c1c2-d1d2-d3d4d5d6d7d8"]
+pub struct PostProcessMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub render_texture: Handle<Image>,
+    #[texture(2)]
+    #[sampler(3)]
+    pub noise_texture: Handle<Image>,
+    #[uniform(4)]
+    pub orientation: Mat4,
+    #[texture(5)]
+    #[sampler(6)]
+    pub palette_texture: Handle<Image>,
+    /// x: effect id, see `PostEffect::shader_id`
+    #[uniform(7)]
+    pub effect: Vec4,
+    #[uniform(8)]
+    pub params: Vec4,
+    #[texture(9)]
+    #[sampler(10)]
+    pub highlight_texture: Handle<Image>,
+    #[texture(11)]
+    #[sampler(12)]
+    pub hud_texture: Handle<Image>,
+}
+
+impl Material for PostProcessMaterial {
+    fn fragment_shader() -> bevy::render::render_resource::ShaderRef {
+        "shaders/postprocess_material.wgsl".into()
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct PostPass {
+    /// `None` for the plain copy pass used when every effect is disabled.
+    effect: Option<PostEffect>,
+}
+
+struct PostProcessPasses {
+    /// The enabled effects the current passes were built for.
+    effects: Option<Vec<PostEffect>>,
+    entities: Vec<Entity>,
+    /// Intermediate textures, reused between rebuilds.
+    textures: Vec<Handle<Image>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+const RENDER_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+#[cfg(not(target_arch = "wasm32"))]
+const RENDER_TEXTURE_FORMAT: TextureFormat = TextureFormat::Bgra8UnormSrgb;
+
+pub(crate) fn create_render_texture(size: Extent3d) -> Image {
+    Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size,
+            dimension: TextureDimension::D2,
+            format: RENDER_TEXTURE_FORMAT,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        sampler_descriptor: ImageSampler::Descriptor(SamplerDescriptor {
+            mag_filter: bevy::render::render_resource::FilterMode::Nearest,
+            min_filter: bevy::render::render_resource::FilterMode::Nearest,
+            ..default()
+        }),
+        ..default()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rebuild_passes(
+    mut commands: Commands,
+    chain: Res<PostProcessChain>,
+    palette_settings: Res<PaletteSettings>,
+    cam_transform: Res<MainCameraTransform>,
+    world_target: Option<Res<WorldRenderTarget>>,
+    highlight_mask: Option<Res<HighlightMask>>,
+    hud_texture: Option<Res<HudTexture>>,
+    mut passes: ResMut<PostProcessPasses>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<PostProcessMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    assets: Res<AssetServer>,
+) {
+    let effects: Vec<PostEffect> = chain.enabled().map(|slot| slot.effect).collect();
+    if passes.effects.as_ref() == Some(&effects) {
+        return;
+    }
+
+    // wait for every texture the passes read from, rather than binding nothing
+    let (world_target, highlight_texture, hud_texture, palette_texture) = match (
+        world_target,
+        highlight_mask,
+        hud_texture,
+        palette_settings.active_palette(),
+    ) {
+        (Some(world_target), Some(highlight_mask), Some(hud_texture), Some(palette)) => (
+            world_target,
+            highlight_mask.0.clone(),
+            hud_texture.0.clone(),
+            palette.clone(),
+        ),
+        _ => return,
+    };
+
+    for entity in passes.entities.drain(..) {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let pass_effects: Vec<Option<PostEffect>> = if effects.is_empty() {
+        vec![None]
+    } else {
+        effects.iter().copied().map(Some).collect()
+    };
+
+    let world_size = images
+        .get(&world_target.0)
+        .map(|image| image.texture_descriptor.size)
+        .unwrap_or(Extent3d {
+            width: 64,
+            height: 64,
+            ..default()
+        });
+
+    while passes.textures.len() + 1 < pass_effects.len() {
+        let mut image = create_render_texture(world_size);
+        image.resize(world_size);
+        passes.textures.push(images.add(image));
+    }
+
+    let plane_handle = meshes.add(Mesh::from(Plane { size: 1.0 }));
+    let noise_texture: Handle<Image> = assets.load("textures/blue_noise.png");
+
+    let mut input = world_target.0.clone();
+    for (index, effect) in pass_effects.iter().enumerate() {
+        let layer = RenderLayers::layer(PASS_LAYER_OFFSET + index as u8);
+        let is_last = index + 1 == pass_effects.len();
+        let target = if is_last {
+            RenderTarget::default()
+        } else {
+            RenderTarget::Image(passes.textures[index].clone())
+        };
+
+        let material_handle = materials.add(PostProcessMaterial {
+            render_texture: input.clone(),
+            noise_texture: noise_texture.clone(),
+            orientation: cam_transform.value,
+            palette_texture: palette_texture.clone(),
+            effect: Vec4::new(effect.map_or(0.0, PostEffect::shader_id), 0.0, 0.0, 0.0),
+            params: chain.params(*effect),
+            highlight_texture: highlight_texture.clone(),
+            hud_texture: hud_texture.clone(),
+        });
+
+        let quad = commands
+            .spawn_bundle(MaterialMeshBundle {
+                material: material_handle,
+                mesh: plane_handle.clone(),
+                transform: Transform::from_rotation(
+                    Quat::from_rotation_z(PI) * Quat::from_rotation_x(PI / 2.0),
+                ),
+                ..default()
+            })
+            .insert(PostPass { effect: *effect })
+            .insert(layer)
+            .id();
+
+        let camera = commands
+            .spawn_bundle(Camera3dBundle {
+                camera_3d: Camera3d {
+                    clear_color: ClearColorConfig::Custom(Color::BLACK),
+                    ..default()
+                },
+                camera: Camera {
+                    priority: index as isize,
+                    target: target.clone(),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, 2.0)
+                    .looking_at(Vec3::new(0.0, 0.0, 0.0), Vec3::Y),
+                projection: bevy::render::camera::Projection::Orthographic(
+                    OrthographicProjection {
+                        left: -0.5,
+                        right: 0.5,
+                        bottom: -0.5,
+                        top: 0.5,
+                        scaling_mode: bevy::render::camera::ScalingMode::None,
+                        ..default()
+                    },
+                ),
+                ..default()
+            })
+            .insert(layer)
+            .id();
+
+        passes.entities.push(quad);
+        passes.entities.push(camera);
+
+        if let RenderTarget::Image(output) = target {
+            input = output;
+        }
+    }
+
+    passes.effects = Some(effects);
+}
+
+fn update_postprocess(
+    cam_transform: Res<MainCameraTransform>,
+    chain: Res<PostProcessChain>,
+    palette_settings: Res<PaletteSettings>,
+    mut materials: ResMut<Assets<PostProcessMaterial>>,
+    query: Query<(&PostPass, &Handle<PostProcessMaterial>)>,
+) {
+    // every write re-uploads the material, so leave them alone while nothing moves
+    if !cam_transform.is_changed() && !chain.is_changed() && !palette_settings.is_changed() {
+        return;
+    }
+
+    for (pass, handle) in query.iter() {
+        let mat = &mut materials.get_mut(handle);
+
+        if let Some(mat) = mat {
+            mat.orientation = cam_transform.value;
+            mat.params = chain.params(pass.effect);
+
+            if palette_settings.is_changed() {
+                if let Some(palette) = palette_settings.active_palette() {
+                    mat.palette_texture = palette.clone();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    clock::GameClock,
+    economy::{Economy, Supplies},
+    objectives::{ObjectiveCompleted, Progress},
+    planetoid::{transform::PlanetoidTransform, Planetoid},
+    state::{AppState, WorldParams},
+    stats::{Statistics, WorldStats},
+    structures::{PlaceStructure, Structure},
+};
+
+/// Where the single save slot lives, in the working directory.
+const SAVE_PATH: &str = "save.ron";
+
+pub(crate) struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SavedGame>()
+            .init_resource::<Autosave>()
+            .add_event::<SaveRequested>()
+            .add_system_set(SystemSet::on_enter(AppState::Title).with_system(read_saved_game))
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_autosave))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(restore_saved_game)
+                    .with_system(autosave.after(restore_saved_game)),
+            )
+            // saving from the pause menu happens outside of Playing
+            .add_system(write_saved_game);
+    }
+}
+
+/// The caretaker's progress. The world itself is regenerated from its parameters on load,
+/// so this holds what can't be: time passed, everything earned along the way, what the
+/// caretaker built and the objectives they completed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct SaveGame {
+    pub(crate) params: WorldParams,
+    pub(crate) elapsed: f32,
+    pub(crate) stock: Supplies,
+    #[serde(default)]
+    pub(crate) structures: Vec<PlaceStructure>,
+    /// Ids of the completed objectives, in the order they were completed.
+    #[serde(default)]
+    pub(crate) completed_objectives: Vec<String>,
+    #[serde(default)]
+    pub(crate) stats: Statistics,
+}
+
+/// The game in the save slot, if there is one, as of the last time the title screen was shown.
+#[derive(Default)]
+pub(crate) struct SavedGame(pub(crate) Option<SaveGame>);
+
+/// A save to restore once the world it belongs to has been set up.
+pub(crate) struct PendingLoad(pub(crate) SaveGame);
+
+/// Asks for the current game to be written to the save slot.
+pub(crate) struct SaveRequested;
+
+/// The day the game was on last frame, to save whenever a new one starts.
+#[derive(Default)]
+struct Autosave {
+    day: Option<u32>,
+}
+
+/// Reads a RON file written by `write_ron_file`, or nothing if it's missing or unreadable.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_ron_file<T: DeserializeOwned>(path: &str) -> Option<T> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    match ron::from_str(&contents) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            bevy::log::warn!("ignoring unreadable {}: {}", path, error);
+            None
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_ron_file<T: Serialize>(path: &str, value: &T) -> anyhow::Result<()> {
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+// there is no file system to save to on the web
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn read_ron_file<T: DeserializeOwned>(_path: &str) -> Option<T> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn write_ron_file<T: Serialize>(_path: &str, _value: &T) -> anyhow::Result<()> {
+    Ok(())
+}
+
+fn read_saved_game(mut saved: ResMut<SavedGame>) {
+    saved.0 = read_ron_file(SAVE_PATH);
+}
+
+fn reset_autosave(mut autosave: ResMut<Autosave>) {
+    *autosave = Autosave::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restore_saved_game(
+    mut commands: Commands,
+    pending: Option<Res<PendingLoad>>,
+    mut clock: ResMut<GameClock>,
+    mut economy: ResMut<Economy>,
+    mut progress: ResMut<Progress>,
+    mut stats: ResMut<WorldStats>,
+    mut place_events: EventWriter<PlaceStructure>,
+    mut completed_events: EventWriter<ObjectiveCompleted>,
+) {
+    let save = match pending {
+        Some(pending) => &pending.0,
+        None => return,
+    };
+
+    clock.elapsed = save.elapsed;
+    economy.stock = save.stock;
+    stats.0 = save.stats.clone();
+    place_events.send_batch(save.structures.iter().cloned());
+    progress.completed = save.completed_objectives.clone();
+    completed_events.send_batch(
+        save.completed_objectives
+            .iter()
+            .map(|id| ObjectiveCompleted {
+                id: id.clone(),
+                restored: true,
+            }),
+    );
+    commands.remove_resource::<PendingLoad>();
+}
+
+fn autosave(
+    clock: Res<GameClock>,
+    mut autosave: ResMut<Autosave>,
+    mut save_events: EventWriter<SaveRequested>,
+) {
+    // a fresh game doesn't overwrite the slot until it has gotten anywhere
+    let day = clock.day();
+    if autosave.day.map_or(false, |seen| seen != day) {
+        save_events.send(SaveRequested);
+    }
+    autosave.day = Some(day);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_saved_game(
+    mut save_events: EventReader<SaveRequested>,
+    params: Res<WorldParams>,
+    clock: Res<GameClock>,
+    economy: Res<Economy>,
+    progress: Res<Progress>,
+    stats: Res<WorldStats>,
+    structures: Query<(&Structure, &PlanetoidTransform, &Parent)>,
+    planetoids: Query<&Planetoid>,
+) {
+    if save_events.iter().count() == 0 {
+        return;
+    }
+
+    let save = SaveGame {
+        params: params.clone(),
+        elapsed: clock.elapsed,
+        stock: economy.stock,
+        structures: structures
+            .iter()
+            .filter_map(|(structure, transform, parent)| {
+                Some(PlaceStructure {
+                    kind: structure.kind,
+                    planetoid: planetoids.get(parent.get()).ok()?.index,
+                    sphere_coords: transform.sphere_coords,
+                    rotation: transform.rotation,
+                })
+            })
+            .collect(),
+        completed_objectives: progress.completed.clone(),
+        stats: stats.0.clone(),
+    };
+    match write_ron_file(SAVE_PATH, &save) {
+        Ok(()) => bevy::log::info!("saved game on day {}", clock.day() + 1),
+        Err(error) => bevy::log::warn!("failed to save game: {}", error),
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashSet;
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::{
+        camera::RenderTarget, mesh::skinning::SkinnedMesh, render_resource::Extent3d,
+        view::RenderLayers,
+    },
+};
+use bevy_mod_raycast::RayCastSource;
+
+use crate::{
+    camera::{CameraFocus, MainCamera},
+    creature::Creature,
+    planetoid::{
+        transform::{cartesian_to_normalized_sphere, SurfaceIndex},
+        Planetoid,
+    },
+    postprocess::create_render_texture,
+    state::AppState,
+    PlanetoidRaycastSet,
+};
+
+/// How far from the cursor's surface point a creature can be and still count as hovered,
+/// in radians. Creatures are only a couple of pixels big, and their animated scenes aren't
+/// raycast, so they're picked by distance instead.
+const HOVER_RADIUS: f32 = 0.12;
+
+pub(crate) struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighlightRenderLayer(RenderLayers::layer(2)))
+            .insert_resource(CursorPick::default())
+            .add_startup_system(setup_highlight_mask)
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(update_cursor_pick)
+                    .with_system(update_hovered.after(update_cursor_pick))
+                    .with_system(select_on_click.after(update_cursor_pick)),
+            )
+            .add_system(attach_highlight_camera)
+            .add_system(attach_planetoid_occluder)
+            .add_system(sync_highlight_proxies.after(update_hovered).after(select_on_click));
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct Hovered;
+
+#[derive(Component)]
+pub(crate) struct Selected;
+
+/// Layer only seen by the highlight camera, which renders flat colored silhouettes of
+/// hovered and selected entities into the mask used by the highlight post effect.
+pub(crate) struct HighlightRenderLayer(pub(crate) RenderLayers);
+
+pub(crate) struct HighlightMask(pub(crate) Handle<Image>);
+
+/// What is currently under the cursor, as seen by the planetoid raycast.
+#[derive(Default)]
+pub(crate) struct CursorPick {
+    /// The planetoid under the cursor.
+    pub(crate) planetoid: Option<Entity>,
+    /// Normalized sphere coordinates of the surface of `planetoid` under the cursor. Nothing
+    /// in the system view, where planetoids are too small to work on.
+    pub(crate) surface: Option<Vec2>,
+    /// Entity directly hit by the ray, if it isn't the planetoid itself.
+    pub(crate) entity: Option<Entity>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HighlightKind {
+    Hovered,
+    Selected,
+}
+
+#[derive(Component)]
+struct HighlightProxy {
+    owner: Entity,
+    kind: HighlightKind,
+}
+
+#[derive(Component)]
+struct HighlightCamera;
+
+#[derive(Component)]
+struct PlanetoidOccluder;
+
+struct HighlightMaterials {
+    hovered: Handle<StandardMaterial>,
+    selected: Handle<StandardMaterial>,
+    occluder: Handle<StandardMaterial>,
+}
+
+fn setup_highlight_mask(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let size = Extent3d {
+        width: 64,
+        height: 64,
+        ..default()
+    };
+
+    let mut image = create_render_texture(size);
+    image.resize(size);
+
+    commands.insert_resource(HighlightMask(images.add(image)));
+
+    let mut flat = |color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            unlit: true,
+            ..default()
+        })
+    };
+
+    commands.insert_resource(HighlightMaterials {
+        hovered: flat(Color::WHITE),
+        selected: flat(Color::rgb(1.0, 0.85, 0.1)),
+        occluder: flat(Color::BLACK),
+    });
+}
+
+fn attach_highlight_camera(
+    mut commands: Commands,
+    mask: Res<HighlightMask>,
+    highlight_render_layer: Res<HighlightRenderLayer>,
+    query: Query<Entity, Added<MainCamera>>,
+) {
+    for main_camera in query.iter() {
+        let highlight_camera = commands
+            .spawn_bundle(Camera3dBundle {
+                camera_3d: Camera3d {
+                    clear_color: ClearColorConfig::Custom(Color::NONE),
+                    ..default()
+                },
+                camera: Camera {
+                    priority: -2,
+                    target: RenderTarget::Image(mask.0.clone()),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(highlight_render_layer.0)
+            .insert(HighlightCamera)
+            .id();
+
+        commands.entity(main_camera).add_child(highlight_camera);
+    }
+}
+
+fn attach_planetoid_occluder(
+    mut commands: Commands,
+    highlight_materials: Res<HighlightMaterials>,
+    highlight_render_layer: Res<HighlightRenderLayer>,
+    query: Query<(Entity, &Handle<Mesh>), Added<Planetoid>>,
+) {
+    for (planetoid, mesh) in query.iter() {
+        let occluder = commands
+            .spawn_bundle(MaterialMeshBundle {
+                mesh: mesh.clone(),
+                material: highlight_materials.occluder.clone(),
+                ..default()
+            })
+            .insert(PlanetoidOccluder)
+            .insert(highlight_render_layer.0)
+            .id();
+
+        commands.entity(planetoid).add_child(occluder);
+    }
+}
+
+fn update_cursor_pick(
+    mut pick: ResMut<CursorPick>,
+    focus: Res<CameraFocus>,
+    sources: Query<&RayCastSource<PlanetoidRaycastSet>, With<MainCamera>>,
+    planetoids: Query<&GlobalTransform, With<Planetoid>>,
+) {
+    pick.planetoid = None;
+    pick.surface = None;
+    pick.entity = None;
+
+    let intersections = match sources.iter().next().and_then(|s| s.intersect_list()) {
+        Some(intersections) => intersections,
+        None => return,
+    };
+
+    for (entity, intersection) in intersections {
+        if let Ok(transform) = planetoids.get(*entity) {
+            pick.planetoid = Some(*entity);
+            if !focus.system_view {
+                let on_planetoid = transform
+                    .compute_matrix()
+                    .inverse()
+                    .transform_point3(intersection.position());
+                pick.surface = Some(cartesian_to_normalized_sphere(on_planetoid));
+            }
+            // anything further away is hidden behind the planetoid
+            break;
+        } else if pick.entity.is_none() && !focus.system_view {
+            pick.entity = Some(*entity);
+        }
+    }
+}
+
+fn update_hovered(
+    mut commands: Commands,
+    pick: Res<CursorPick>,
+    index: Res<SurfaceIndex>,
+    creatures: Query<(), With<Creature>>,
+    hovered: Query<Entity, With<Hovered>>,
+) {
+    let target = pick
+        .entity
+        .filter(|entity| creatures.get(*entity).is_ok())
+        .or_else(|| {
+            index
+                .nearest(pick.planetoid?, pick.surface?, HOVER_RADIUS, |entity| {
+                    creatures.get(*entity).is_ok()
+                })
+                .map(|(entity, _)| entity)
+        });
+
+    for entity in hovered.iter() {
+        if Some(entity) != target {
+            commands.entity(entity).remove::<Hovered>();
+        }
+    }
+
+    if let Some(target) = target {
+        if hovered.get(target).is_err() {
+            commands.entity(target).insert(Hovered);
+        }
+    }
+}
+
+fn select_on_click(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    hovered: Query<Entity, With<Hovered>>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // clicking empty ground keeps the selection, so it can be given a target
+    if let Some(target) = hovered.iter().next() {
+        for entity in selected.iter() {
+            commands.entity(entity).remove::<Selected>();
+        }
+        commands.entity(target).insert(Selected);
+    }
+}
+
+/// Gives every mesh of a hovered or selected entity's scene a flat colored copy on the
+/// highlight layer, skinned like the mesh so it follows the animation.
+fn sync_highlight_proxies(
+    mut commands: Commands,
+    highlight_materials: Res<HighlightMaterials>,
+    highlight_render_layer: Res<HighlightRenderLayer>,
+    highlighted: Query<(Entity, Option<&Selected>), Or<(With<Hovered>, With<Selected>)>>,
+    children: Query<&Children>,
+    meshes: Query<(&Handle<Mesh>, Option<&SkinnedMesh>), Without<HighlightProxy>>,
+    proxies: Query<(Entity, &HighlightProxy)>,
+) {
+    let mut proxied = HashSet::new();
+
+    for (proxy, highlight) in proxies.iter() {
+        let wanted = highlighted.get(highlight.owner).ok().map(|(_, selected)| {
+            if selected.is_some() {
+                HighlightKind::Selected
+            } else {
+                HighlightKind::Hovered
+            }
+        });
+
+        if wanted == Some(highlight.kind) {
+            proxied.insert(highlight.owner);
+        } else {
+            commands.entity(proxy).despawn_recursive();
+        }
+    }
+
+    for (owner, selected) in highlighted.iter() {
+        if proxied.contains(&owner) {
+            continue;
+        }
+
+        let (kind, material) = if selected.is_some() {
+            (HighlightKind::Selected, highlight_materials.selected.clone())
+        } else {
+            (HighlightKind::Hovered, highlight_materials.hovered.clone())
+        };
+
+        // until the scene has spawned there's nothing to copy, so this is tried again
+        let mut stack = vec![owner];
+        while let Some(entity) = stack.pop() {
+            if let Ok(entity_children) = children.get(entity) {
+                stack.extend(entity_children.iter());
+            }
+            let (mesh, skin) = match meshes.get(entity) {
+                Ok(mesh) => mesh,
+                Err(_) => continue,
+            };
+
+            let mut proxy = commands.spawn_bundle(MaterialMeshBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                ..default()
+            });
+            proxy
+                .insert(HighlightProxy { owner, kind })
+                .insert(highlight_render_layer.0);
+            if let Some(skin) = skin {
+                proxy.insert(skin.clone());
+            }
+            let proxy = proxy.id();
+
+            commands.entity(entity).add_child(proxy);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    clock::GameClock,
+    hud::{HudCanvas, HUD_DIM, HUD_TEXT, HUD_WARN},
+    postprocess::{
+        chain::{PaletteSettings, PostEffect},
+        PostProcessChain,
+    },
+    save::{PendingLoad, SaveRequested, SavedGame},
+};
+
+use super::{AppState, WorldParams};
+
+const SPIN_OPTIONS: [(&str, f32); 3] = [("SLOW", 0.05), ("NORMAL", 0.1), ("FAST", 0.2)];
+const LAND_OPTIONS: [(&str, f32); 3] = [("FLAT", 1.0), ("NORMAL", 2.0), ("RUGGED", 3.5)];
+const WORLD_OPTIONS: [usize; 3] = [1, 2, 3];
+const MOON_OPTIONS: [usize; 3] = [0, 1, 2];
+const MAX_SEED_DIGITS: usize = 8;
+
+#[derive(Default)]
+pub(crate) struct MenuCursor(usize);
+
+pub(crate) struct NewGameForm {
+    seed: String,
+    spin: usize,
+    land: usize,
+    worlds: usize,
+    moons: usize,
+}
+
+impl Default for NewGameForm {
+    fn default() -> Self {
+        Self {
+            seed: "1".to_string(),
+            spin: 1,
+            land: 1,
+            worlds: 0,
+            moons: 1,
+        }
+    }
+}
+
+enum MenuInput {
+    Confirm,
+    Back,
+    Left,
+    Right,
+}
+
+/// Returns true once per key press, consuming it so a menu opened by this press
+/// doesn't react to it as well.
+fn take(keys: &mut Input<KeyCode>, key: KeyCode) -> bool {
+    keys.clear_just_pressed(key)
+}
+
+fn navigate(keys: &mut Input<KeyCode>, cursor: &mut MenuCursor, items: usize) -> Option<MenuInput> {
+    if take(keys, KeyCode::Up) {
+        cursor.0 = (cursor.0 + items - 1) % items;
+    }
+    if take(keys, KeyCode::Down) {
+        cursor.0 = (cursor.0 + 1) % items;
+    }
+
+    if take(keys, KeyCode::Return) || take(keys, KeyCode::Space) {
+        Some(MenuInput::Confirm)
+    } else if take(keys, KeyCode::Escape) {
+        Some(MenuInput::Back)
+    } else if take(keys, KeyCode::Left) {
+        Some(MenuInput::Left)
+    } else if take(keys, KeyCode::Right) {
+        Some(MenuInput::Right)
+    } else {
+        None
+    }
+}
+
+fn draw_menu(canvas: &mut HudCanvas, title: &str, items: &[String], cursor: usize) {
+    canvas.rect(0, 0, canvas.width(), canvas.height(), [0, 0, 0, 200]);
+    canvas.text_centered(6, title, HUD_WARN);
+
+    for (i, item) in items.iter().enumerate() {
+        let y = 20 + i as i32 * 7;
+        if i == cursor {
+            canvas.text(1, y, ">", HUD_WARN);
+            canvas.text(5, y, item, HUD_TEXT);
+        } else {
+            canvas.text(5, y, item, HUD_DIM);
+        }
+    }
+}
+
+pub(crate) fn reset_menu_cursor(mut cursor: ResMut<MenuCursor>) {
+    cursor.0 = 0;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn title_menu(
+    mut commands: Commands,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut cursor: ResMut<MenuCursor>,
+    mut canvas: ResMut<HudCanvas>,
+    mut state: ResMut<State<AppState>>,
+    mut params: ResMut<WorldParams>,
+    saved: Res<SavedGame>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // continuing is only on offer with something in the save slot
+    let mut items = vec!["NEW GAME", "SETTINGS", "QUIT"];
+    let offset = if saved.0.is_some() {
+        items.insert(0, "CONTINUE");
+        1
+    } else {
+        0
+    };
+    let items: Vec<String> = items.into_iter().map(String::from).collect();
+
+    if let Some(MenuInput::Confirm) = navigate(&mut keys, &mut cursor, items.len()) {
+        match (cursor.0, &saved.0) {
+            (0, Some(save)) => {
+                *params = save.params.clone();
+                bevy::log::info!("continuing saved game: {:?}", *params);
+                commands.insert_resource(PendingLoad(save.clone()));
+                let _ = state.set(AppState::Playing);
+            }
+            (row, _) if row == offset => {
+                let _ = state.set(AppState::NewGame);
+            }
+            (row, _) if row == offset + 1 => {
+                let _ = state.push(AppState::Settings);
+            }
+            _ => exit.send(AppExit),
+        }
+    }
+
+    draw_menu(&mut canvas, "PLANTOID", &items, cursor.0);
+}
+
+pub(crate) fn new_game_menu(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut cursor: ResMut<MenuCursor>,
+    mut form: ResMut<NewGameForm>,
+    mut canvas: ResMut<HudCanvas>,
+    mut state: ResMut<State<AppState>>,
+    mut params: ResMut<WorldParams>,
+) {
+    for event in characters.iter() {
+        if cursor.0 == 0 && event.char.is_ascii_digit() && form.seed.len() < MAX_SEED_DIGITS {
+            form.seed.push(event.char);
+        }
+    }
+    if cursor.0 == 0 && take(&mut keys, KeyCode::Back) {
+        form.seed.pop();
+    }
+
+    let items = [
+        format!("SEED {}", form.seed),
+        format!("SPIN {}", SPIN_OPTIONS[form.spin].0),
+        format!("LAND {}", LAND_OPTIONS[form.land].0),
+        format!("WORLDS {}", WORLD_OPTIONS[form.worlds]),
+        format!("MOONS {}", MOON_OPTIONS[form.moons]),
+        "START".to_string(),
+    ];
+
+    match navigate(&mut keys, &mut cursor, items.len()) {
+        Some(MenuInput::Left) => match cursor.0 {
+            1 => form.spin = form.spin.saturating_sub(1),
+            2 => form.land = form.land.saturating_sub(1),
+            3 => form.worlds = form.worlds.saturating_sub(1),
+            4 => form.moons = form.moons.saturating_sub(1),
+            _ => {}
+        },
+        Some(MenuInput::Right) => match cursor.0 {
+            1 => form.spin = (form.spin + 1).min(SPIN_OPTIONS.len() - 1),
+            2 => form.land = (form.land + 1).min(LAND_OPTIONS.len() - 1),
+            3 => form.worlds = (form.worlds + 1).min(WORLD_OPTIONS.len() - 1),
+            4 => form.moons = (form.moons + 1).min(MOON_OPTIONS.len() - 1),
+            _ => {}
+        },
+        Some(MenuInput::Confirm) => {
+            *params = WorldParams {
+                seed: form.seed.parse().unwrap_or(0),
+                rotation_speed: SPIN_OPTIONS[form.spin].1,
+                terrain_amplitude: LAND_OPTIONS[form.land].1,
+                planetoids: WORLD_OPTIONS[form.worlds],
+                moons: MOON_OPTIONS[form.moons],
+                ..default()
+            };
+            bevy::log::info!("starting new game: {:?}", *params);
+            let _ = state.set(AppState::Playing);
+        }
+        Some(MenuInput::Back) => {
+            let _ = state.set(AppState::Title);
+        }
+        None => {}
+    }
+
+    draw_menu(&mut canvas, "NEW WORLD", &items, cursor.0);
+}
+
+pub(crate) fn pause_menu(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut cursor: ResMut<MenuCursor>,
+    mut canvas: ResMut<HudCanvas>,
+    mut state: ResMut<State<AppState>>,
+    mut save_events: EventWriter<SaveRequested>,
+) {
+    let items = ["RESUME", "SAVE", "SETTINGS", "TITLE"].map(String::from);
+
+    match navigate(&mut keys, &mut cursor, items.len()) {
+        Some(MenuInput::Back) => {
+            let _ = state.pop();
+        }
+        Some(MenuInput::Confirm) => match cursor.0 {
+            0 => {
+                let _ = state.pop();
+            }
+            1 => {
+                save_events.send(SaveRequested);
+                let _ = state.pop();
+            }
+            2 => {
+                let _ = state.push(AppState::Settings);
+            }
+            _ => {
+                let _ = state.replace(AppState::Title);
+            }
+        },
+        _ => {}
+    }
+
+    draw_menu(&mut canvas, "PAUSED", &items, cursor.0);
+}
+
+pub(crate) fn settings_menu(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut cursor: ResMut<MenuCursor>,
+    mut canvas: ResMut<HudCanvas>,
+    mut state: ResMut<State<AppState>>,
+    mut chain: ResMut<PostProcessChain>,
+    mut palette_settings: ResMut<PaletteSettings>,
+) {
+    const TOGGLES: [(&str, PostEffect); 4] = [
+        ("DITHER", PostEffect::Dither),
+        ("SCAN", PostEffect::Scanlines),
+        ("VIGN", PostEffect::Vignette),
+        ("GLOW", PostEffect::Glow),
+    ];
+
+    let palette_count = palette_settings.palettes.len().max(1);
+    let mut items = vec![format!("PAL {}", palette_settings.active + 1)];
+    for (name, effect) in TOGGLES {
+        let enabled = chain.enabled().any(|slot| slot.effect == effect);
+        items.push(format!("{} {}", name, if enabled { "ON" } else { "OFF" }));
+    }
+    items.push("BACK".to_string());
+
+    let input = navigate(&mut keys, &mut cursor, items.len());
+    let row = cursor.0;
+    match input {
+        Some(MenuInput::Back) => {
+            let _ = state.pop();
+        }
+        Some(MenuInput::Confirm) if row == items.len() - 1 => {
+            let _ = state.pop();
+        }
+        Some(MenuInput::Left) if row == 0 => {
+            palette_settings.active = (palette_settings.active + palette_count - 1) % palette_count;
+        }
+        Some(MenuInput::Right | MenuInput::Confirm) if row == 0 => {
+            palette_settings.active = (palette_settings.active + 1) % palette_count;
+        }
+        Some(_) if (1..=TOGGLES.len()).contains(&row) => {
+            chain.toggle(TOGGLES[row - 1].1);
+        }
+        _ => {}
+    }
+
+    draw_menu(&mut canvas, "SETTINGS", &items, cursor.0);
+}
+
+pub(crate) fn game_over_menu(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut cursor: ResMut<MenuCursor>,
+    mut canvas: ResMut<HudCanvas>,
+    mut state: ResMut<State<AppState>>,
+    clock: Res<GameClock>,
+) {
+    let items = ["NEW GAME", "TITLE"].map(String::from);
+
+    if let Some(MenuInput::Confirm) = navigate(&mut keys, &mut cursor, items.len()) {
+        let next = if cursor.0 == 0 {
+            AppState::NewGame
+        } else {
+            AppState::Title
+        };
+        let _ = state.set(next);
+    }
+
+    draw_menu(&mut canvas, "EXTINCT", &items, cursor.0);
+    canvas.text_centered(13, &format!("DAY {}", clock.day() + 1), HUD_DIM);
+}
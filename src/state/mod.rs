@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::creature::Creature;
+
+use self::menu::{
+    game_over_menu, new_game_menu, pause_menu, reset_menu_cursor, settings_menu, title_menu,
+    MenuCursor, NewGameForm,
+};
+
+mod menu;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum AppState {
+    Title,
+    NewGame,
+    Playing,
+    Paused,
+    Settings,
+    GameOver,
+}
+
+/// Marks entities belonging to the current world, which are despawned when leaving it.
+#[derive(Component)]
+pub(crate) struct InGame;
+
+/// Parameters the world is generated from, chosen on the new game screen.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct WorldParams {
+    pub(crate) seed: u64,
+    /// Radians per second the planetoid spins around its axis.
+    pub(crate) rotation_speed: f32,
+    /// Scale of the terrain noise displacement.
+    pub(crate) terrain_amplitude: f32,
+    /// Planetoids in the system. The first one is generated from these parameters as they
+    /// are, the others from variations of them.
+    #[serde(default = "one_planetoid")]
+    pub(crate) planetoids: usize,
+    /// Moons circling each planetoid.
+    #[serde(default)]
+    pub(crate) moons: usize,
+    /// Color the air scatters the sunlight in, seen along the rim and the terminator.
+    #[serde(default = "blue_sky")]
+    pub(crate) atmosphere_color: [f32; 3],
+    /// How much sunlight the air scatters, with 0.0 for no atmosphere at all.
+    #[serde(default = "one_atmosphere")]
+    pub(crate) atmosphere_thickness: f32,
+}
+
+fn one_planetoid() -> usize {
+    1
+}
+
+fn blue_sky() -> [f32; 3] {
+    [0.16, 0.68, 1.0]
+}
+
+fn one_atmosphere() -> f32 {
+    1.0
+}
+
+impl Default for WorldParams {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            rotation_speed: 0.1,
+            terrain_amplitude: 2.0,
+            planetoids: 1,
+            moons: 0,
+            atmosphere_color: blue_sky(),
+            atmosphere_thickness: one_atmosphere(),
+        }
+    }
+}
+
+/// How much faster each planetoid after the first spins, how much rougher it is, and how much
+/// thicker its air is, in turn.
+const PLANETOID_VARIATIONS: [(f32, f32, f32); 3] =
+    [(1.0, 1.0, 1.0), (1.6, 0.6, 0.4), (0.6, 1.5, 1.8)];
+/// Air of each planetoid after the first, in turn.
+const ATMOSPHERE_COLORS: [[f32; 3]; 2] = [[1.0, 0.47, 0.66], [1.0, 0.64, 0.0]];
+
+impl WorldParams {
+    /// Parameters of the planetoid at `index` in the system.
+    pub(crate) fn planetoid(&self, index: usize) -> WorldParams {
+        let (spin, roughness, air) = PLANETOID_VARIATIONS[index % PLANETOID_VARIATIONS.len()];
+        WorldParams {
+            seed: self.seed.wrapping_add(index as u64 * 7919),
+            rotation_speed: self.rotation_speed * spin,
+            terrain_amplitude: self.terrain_amplitude * roughness,
+            planetoids: self.planetoids,
+            moons: self.moons,
+            atmosphere_color: match index {
+                0 => self.atmosphere_color,
+                _ => ATMOSPHERE_COLORS[(index - 1) % ATMOSPHERE_COLORS.len()],
+            },
+            atmosphere_thickness: self.atmosphere_thickness * air,
+        }
+    }
+
+    /// Offset into the terrain noise, so different seeds give different landscapes.
+    pub(crate) fn terrain_offset(&self) -> Vec2 {
+        let hashed = self.seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        Vec2::new(
+            (hashed & 0xffff) as f32 / 65536.0 * 100.0,
+            ((hashed >> 16) & 0xffff) as f32 / 65536.0 * 100.0,
+        )
+    }
+}
+
+/// Randomness for world simulation, seeded from the world seed so worlds replay alike.
+pub(crate) struct WorldRng(pub(crate) SmallRng);
+
+impl Default for WorldRng {
+    fn default() -> Self {
+        Self(SmallRng::seed_from_u64(0))
+    }
+}
+
+/// Tracks whether the current world has had any creatures yet, so an empty world
+/// right after setup isn't mistaken for extinction.
+#[derive(Default)]
+struct ExtinctionWatch {
+    seen_creatures: bool,
+}
+
+pub(crate) struct StatePlugin;
+
+impl Plugin for StatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(AppState::Title)
+            .init_resource::<WorldParams>()
+            .init_resource::<ExtinctionWatch>()
+            .init_resource::<WorldRng>()
+            .init_resource::<MenuCursor>()
+            .init_resource::<NewGameForm>()
+            .add_system_set(SystemSet::on_enter(AppState::Title).with_system(reset_menu_cursor))
+            .add_system_set(SystemSet::on_resume(AppState::Title).with_system(reset_menu_cursor))
+            .add_system_set(SystemSet::on_update(AppState::Title).with_system(title_menu))
+            .add_system_set(
+                SystemSet::on_enter(AppState::NewGame).with_system(reset_menu_cursor),
+            )
+            .add_system_set(SystemSet::on_update(AppState::NewGame).with_system(new_game_menu))
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(reset_extinction_watch)
+                    .with_system(seed_world_rng),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(pause_on_escape)
+                    .with_system(check_extinction),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Playing).with_system(teardown_world))
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(reset_menu_cursor))
+            .add_system_set(SystemSet::on_resume(AppState::Paused).with_system(reset_menu_cursor))
+            .add_system_set(SystemSet::on_update(AppState::Paused).with_system(pause_menu))
+            .add_system_set(
+                SystemSet::on_enter(AppState::Settings).with_system(reset_menu_cursor),
+            )
+            .add_system_set(SystemSet::on_update(AppState::Settings).with_system(settings_menu))
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(reset_menu_cursor),
+            )
+            .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(game_over_menu));
+    }
+}
+
+fn pause_on_escape(mut keys: ResMut<Input<KeyCode>>, mut state: ResMut<State<AppState>>) {
+    if keys.just_pressed(KeyCode::Escape) && state.push(AppState::Paused).is_ok() {
+        // keep the pause menu from reading the same press as "back"
+        keys.clear_just_pressed(KeyCode::Escape);
+    }
+}
+
+fn reset_extinction_watch(mut watch: ResMut<ExtinctionWatch>) {
+    *watch = ExtinctionWatch::default();
+}
+
+fn seed_world_rng(params: Res<WorldParams>, mut rng: ResMut<WorldRng>) {
+    *rng = WorldRng(SmallRng::seed_from_u64(params.seed));
+}
+
+fn check_extinction(
+    mut watch: ResMut<ExtinctionWatch>,
+    mut state: ResMut<State<AppState>>,
+    creatures: Query<(), With<Creature>>,
+) {
+    if !creatures.is_empty() {
+        watch.seen_creatures = true;
+    } else if watch.seen_creatures {
+        bevy::log::info!("all creatures are gone, game over");
+        let _ = state.set(AppState::GameOver);
+    }
+}
+
+fn teardown_world(mut commands: Commands, query: Query<Entity, (With<InGame>, Without<Parent>)>) {
+    // whatever lives on a planetoid goes along with it
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::GameClock,
+    creature::{
+        life::{CreatureBorn, CreatureDied},
+        Creature,
+    },
+    hud::{Announcement, HUD_GOOD},
+    objectives::ObjectiveCompleted,
+    planetoid::transform::{great_circle_distance, PlanetoidTransform},
+    plant::{PlantDied, PlantMatured},
+    save::{read_ron_file, write_ron_file},
+    state::AppState,
+    structures::StructureBuilt,
+};
+
+const ACHIEVEMENTS_PATH: &str = "achievements/default.achievements.ron";
+/// Where the player's profile lives, in the working directory. Unlike the save slot it
+/// outlives every world.
+const PROFILE_PATH: &str = "profile.ron";
+
+pub(crate) struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AchievementBook>()
+            .init_asset_loader::<AchievementLoader>()
+            .init_resource::<WorldStats>()
+            .add_startup_system(load_achievements)
+            .add_startup_system(load_profile)
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_world_stats))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(count_events)
+                    .with_system(count_distance)
+                    .with_system(count_days)
+                    .with_system(
+                        check_achievements
+                            .after(count_events)
+                            .after(count_distance)
+                            .after(count_days),
+                    ),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Playing).with_system(write_profile));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Stat {
+    /// Creatures born to parents, not counting founders.
+    CreaturesBorn,
+    CreaturesDied,
+    PlantsMatured,
+    PlantsDied,
+    DaysSurvived,
+    /// Radians all creatures walked, put together.
+    DistanceTraveled,
+    StructuresBuilt,
+    ObjectivesCompleted,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub(crate) struct Statistics(HashMap<Stat, f32>);
+
+impl Statistics {
+    pub(crate) fn get(&self, stat: Stat) -> f32 {
+        self.0.get(&stat).copied().unwrap_or(0.0)
+    }
+
+    fn add(&mut self, stat: Stat, amount: f32) {
+        *self.0.entry(stat).or_insert(0.0) += amount;
+    }
+}
+
+/// Statistics of the current world, saved along with it.
+#[derive(Default)]
+pub(crate) struct WorldStats(pub(crate) Statistics);
+
+/// Lifetime statistics and earned achievements, kept across worlds in `PROFILE_PATH`.
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct Profile {
+    pub(crate) lifetime: Statistics,
+    /// Ids of the earned achievements, in the order they were earned.
+    pub(crate) achievements: Vec<String>,
+}
+
+/// What it takes to earn an achievement.
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) enum Condition {
+    /// The stat reached the value within a single world.
+    InWorld(Stat, f32),
+    /// The stat reached the value summed over every world played.
+    Lifetime(Stat, f32),
+    All(Vec<Condition>),
+}
+
+impl Condition {
+    fn is_met(&self, world: &Statistics, lifetime: &Statistics) -> bool {
+        match self {
+            Condition::InWorld(stat, value) => world.get(*stat) >= *value,
+            Condition::Lifetime(stat, value) => lifetime.get(*stat) >= *value,
+            Condition::All(conditions) => conditions
+                .iter()
+                .all(|condition| condition.is_met(world, lifetime)),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct Achievement {
+    pub(crate) id: String,
+    /// Short name that fits the HUD.
+    pub(crate) title: String,
+    pub(crate) condition: Condition,
+}
+
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "2d6b8e47-51c3-4a9f-b0e2-7f4c3a1d8e65"]
+pub(crate) struct AchievementBook {
+    pub(crate) achievements: Vec<Achievement>,
+}
+
+#[derive(Default)]
+pub(crate) struct AchievementLoader;
+
+impl AssetLoader for AchievementLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let book: AchievementBook = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(book));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["achievements.ron"]
+    }
+}
+
+pub(crate) struct Achievements(pub(crate) Handle<AchievementBook>);
+
+fn load_achievements(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Achievements(asset_server.load(ACHIEVEMENTS_PATH)));
+}
+
+fn load_profile(mut commands: Commands) {
+    commands.insert_resource(read_ron_file::<Profile>(PROFILE_PATH).unwrap_or_default());
+}
+
+fn save_profile(profile: &Profile) {
+    if let Err(error) = write_ron_file(PROFILE_PATH, profile) {
+        bevy::log::warn!("failed to write profile: {}", error);
+    }
+}
+
+fn write_profile(profile: Res<Profile>) {
+    save_profile(&profile);
+}
+
+fn reset_world_stats(mut stats: ResMut<WorldStats>) {
+    *stats = WorldStats::default();
+}
+
+/// Adds to the stat both in the current world and over the whole profile.
+fn record(world: &mut WorldStats, profile: &mut Profile, stat: Stat, amount: f32) {
+    world.0.add(stat, amount);
+    profile.lifetime.add(stat, amount);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn count_events(
+    mut world: ResMut<WorldStats>,
+    mut profile: ResMut<Profile>,
+    mut born_events: EventReader<CreatureBorn>,
+    mut died_events: EventReader<CreatureDied>,
+    mut matured_events: EventReader<PlantMatured>,
+    mut plant_died_events: EventReader<PlantDied>,
+    mut built_events: EventReader<StructureBuilt>,
+    mut completed_events: EventReader<ObjectiveCompleted>,
+) {
+    let counts = [
+        (
+            Stat::CreaturesBorn,
+            born_events
+                .iter()
+                .filter(|event| event.parents.is_some())
+                .count(),
+        ),
+        (Stat::CreaturesDied, died_events.iter().count()),
+        (Stat::PlantsMatured, matured_events.iter().count()),
+        (Stat::PlantsDied, plant_died_events.iter().count()),
+        (Stat::StructuresBuilt, built_events.iter().count()),
+        (
+            Stat::ObjectivesCompleted,
+            completed_events
+                .iter()
+                .filter(|event| !event.restored)
+                .count(),
+        ),
+    ];
+
+    for (stat, count) in counts {
+        if count > 0 {
+            record(&mut world, &mut profile, stat, count as f32);
+        }
+    }
+}
+
+fn count_distance(
+    mut world: ResMut<WorldStats>,
+    mut profile: ResMut<Profile>,
+    mut last_positions: Local<HashMap<Entity, (Entity, Vec2)>>,
+    creatures: Query<(
+        Entity,
+        &PlanetoidTransform,
+        &Parent,
+        ChangeTrackers<Creature>,
+    )>,
+) {
+    let mut distance = 0.0;
+    let mut positions = HashMap::with_capacity(last_positions.len());
+    for (entity, transform, parent, trackers) in creatures.iter() {
+        // entities are reused, so a new creature may have been something else last frame,
+        // and one sent to another planetoid didn't walk there
+        let last = last_positions
+            .get(&entity)
+            .filter(|(planetoid, _)| !trackers.is_added() && *planetoid == parent.get());
+        if let Some((_, last)) = last {
+            distance += great_circle_distance(*last, transform.sphere_coords);
+        }
+        positions.insert(entity, (parent.get(), transform.sphere_coords));
+    }
+    // creatures that are gone are dropped along the way
+    *last_positions = positions;
+
+    if distance > 0.0 {
+        record(&mut world, &mut profile, Stat::DistanceTraveled, distance);
+    }
+}
+
+fn count_days(clock: Res<GameClock>, mut world: ResMut<WorldStats>, mut profile: ResMut<Profile>) {
+    // a restored world brings its days survived along, so only count days gone by since
+    let days = clock.day() as f32 - world.0.get(Stat::DaysSurvived);
+    if days >= 1.0 {
+        record(&mut world, &mut profile, Stat::DaysSurvived, days);
+    }
+}
+
+fn check_achievements(
+    achievements: Res<Achievements>,
+    books: Res<Assets<AchievementBook>>,
+    world: Res<WorldStats>,
+    mut profile: ResMut<Profile>,
+    mut announcements: EventWriter<Announcement>,
+) {
+    let book = match books.get(&achievements.0) {
+        Some(book) => book,
+        None => return,
+    };
+
+    let mut earned = false;
+    for achievement in book.achievements.iter() {
+        if profile.achievements.contains(&achievement.id)
+            || !achievement.condition.is_met(&world.0, &profile.lifetime)
+        {
+            continue;
+        }
+
+        bevy::log::info!("achievement {} earned", achievement.id);
+        profile.achievements.push(achievement.id.clone());
+        announcements.send(Announcement {
+            title: "ACHIEVED".to_string(),
+            text: achievement.title.clone(),
+            color: HUD_GOOD,
+        });
+        earned = true;
+    }
+
+    if earned {
+        save_profile(&profile);
+    }
+}
@@ -0,0 +1,632 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{GameClock, DAY_LENGTH},
+    creature::{Creature, Needs},
+    economy::{Economy, Supplies},
+    objectives::Unlocks,
+    planetoid::{
+        heightfield::Heightfield,
+        in_daylight,
+        terrain::{biome_at, Biome},
+        transform::{great_circle_distance, PlanetoidTransform, SurfaceIndex},
+        water::Water,
+        Planetoid,
+    },
+    plant::Plant,
+    selection::CursorPick,
+    state::{AppState, InGame},
+    tools::{ActiveTool, Tool},
+    GameWorldRenderLayer,
+};
+
+/// Structures need this many radians of room around them.
+const FOOTPRINT: f32 = 0.05;
+/// Steepest ground a structure can stand on, in height per radian.
+const MAX_SLOPE: f32 = 0.2;
+/// Distance in normalized sphere coordinates the slope is measured over.
+const SLOPE_STEP: f32 = 0.005;
+/// Structures can't be built under more water than this.
+const MAX_BUILD_DEPTH: f32 = 0.002;
+/// Farthest a pump draws water from, in radians.
+const PUMP_REACH: f32 = 0.15;
+/// How far each press of the rotate key turns the structure.
+const ROTATE_STEP: f32 = PI / 4.0;
+
+const PUMP_WATER_PER_DAY: f32 = 8.0;
+const SHELTER_ENERGY_PER_DAY: f32 = 2.0;
+const FEEDER_HUNGER_PER_DAY: f32 = 1.5;
+/// Nutrients the feeder uses up per unit of hunger it sates.
+const FEEDER_NUTRIENTS_PER_HUNGER: f32 = 0.5;
+const SPRINKLER_HEALTH_PER_DAY: f32 = 1.0;
+/// Water the sprinkler uses up per unit of plant health it restores.
+const SPRINKLER_WATER_PER_HEALTH: f32 = 0.2;
+
+pub(crate) struct StructurePlugin;
+
+impl Plugin for StructurePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BuildPlan>()
+            .add_event::<PlaceStructure>()
+            .add_event::<StructureBuilt>()
+            .add_startup_system(setup_structure_assets)
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(reset_build_plan)
+                    .with_system(spawn_ghost),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(adjust_build_plan)
+                    .with_system(check_placement.after(adjust_build_plan))
+                    .with_system(place_structure.after(check_placement))
+                    .with_system(update_ghost.after(check_placement))
+                    .with_system(spawn_structures.after(place_structure))
+                    .with_system(run_structures)
+                    .with_system(light_structures),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub(crate) enum StructureKind {
+    /// Creatures nearby rest and recover energy faster.
+    Shelter,
+    /// Draws water from a nearby lake, river or the sea into storage.
+    Pump,
+    /// Feeds hungry creatures nearby from the nutrient stock.
+    Feeder,
+    /// Waters plants nearby from the water stock.
+    Sprinkler,
+}
+
+impl StructureKind {
+    pub(crate) const ALL: [StructureKind; 4] = [
+        StructureKind::Shelter,
+        StructureKind::Pump,
+        StructureKind::Feeder,
+        StructureKind::Sprinkler,
+    ];
+
+    /// Short name that fits the HUD.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            StructureKind::Shelter => "SHELTR",
+            StructureKind::Pump => "PUMP",
+            StructureKind::Feeder => "FEEDER",
+            StructureKind::Sprinkler => "SPRINK",
+        }
+    }
+
+    pub(crate) fn cost(self) -> Supplies {
+        match self {
+            StructureKind::Shelter => Supplies {
+                nutrients: 5.0,
+                care: 20.0,
+                ..Supplies::NONE
+            },
+            StructureKind::Pump => Supplies {
+                nutrients: 5.0,
+                care: 40.0,
+                ..Supplies::NONE
+            },
+            StructureKind::Feeder => Supplies {
+                nutrients: 10.0,
+                care: 30.0,
+                ..Supplies::NONE
+            },
+            StructureKind::Sprinkler => Supplies {
+                water: 10.0,
+                care: 30.0,
+                ..Supplies::NONE
+            },
+        }
+    }
+
+    /// Radians around the structure its effect reaches.
+    fn radius(self) -> f32 {
+        match self {
+            StructureKind::Shelter | StructureKind::Feeder => 0.2,
+            StructureKind::Pump => PUMP_REACH,
+            StructureKind::Sprinkler => 0.25,
+        }
+    }
+
+    fn allows(self, biome: Biome) -> bool {
+        match self {
+            // pipes freeze in the cold, and there's no ground water to pump in the desert
+            StructureKind::Sprinkler => biome != Biome::Tundra,
+            StructureKind::Pump => biome != Biome::Desert,
+            StructureKind::Shelter | StructureKind::Feeder => true,
+        }
+    }
+
+    /// Width, height and depth of the structure's box.
+    fn size(self) -> Vec3 {
+        match self {
+            StructureKind::Shelter => Vec3::new(0.08, 0.05, 0.08),
+            StructureKind::Pump => Vec3::new(0.03, 0.09, 0.03),
+            StructureKind::Feeder => Vec3::new(0.06, 0.03, 0.04),
+            StructureKind::Sprinkler => Vec3::new(0.02, 0.06, 0.02),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            StructureKind::Shelter => Color::rgb(0.67, 0.32, 0.21),
+            StructureKind::Pump => Color::rgb(0.37, 0.34, 0.31),
+            StructureKind::Feeder => Color::rgb(1.0, 0.64, 0.0),
+            StructureKind::Sprinkler => Color::rgb(0.16, 0.68, 1.0),
+        }
+    }
+
+    /// Color on the night side, with its lights on.
+    fn lit_color(self) -> Color {
+        match self {
+            StructureKind::Shelter => Color::rgb(1.0, 0.93, 0.15),
+            StructureKind::Pump => Color::rgb(0.76, 0.76, 0.78),
+            StructureKind::Feeder => Color::rgb(1.0, 0.8, 0.67),
+            StructureKind::Sprinkler => Color::rgb(0.5, 0.85, 1.0),
+        }
+    }
+
+    fn index(self) -> usize {
+        StructureKind::ALL
+            .iter()
+            .position(|kind| *kind == self)
+            .unwrap()
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct Structure {
+    pub(crate) kind: StructureKind,
+}
+
+/// Builds a structure, paid for or not, e.g. when restoring a save.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PlaceStructure {
+    pub(crate) kind: StructureKind,
+    /// Index of the planetoid in the system, which unlike its entity survives a reload.
+    #[serde(default)]
+    pub(crate) planetoid: usize,
+    pub(crate) sphere_coords: Vec2,
+    pub(crate) rotation: f32,
+}
+
+/// Sent when the caretaker builds a structure, unlike `PlaceStructure` which is also used
+/// for restoring them.
+pub(crate) struct StructureBuilt {
+    pub(crate) kind: StructureKind,
+}
+
+/// Why a structure can't go where the cursor is, labelled short enough for the toolbar.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum PlacementError {
+    /// Not unlocked by an objective yet.
+    Locked,
+    Slope,
+    Biome,
+    Overlap,
+    Underwater,
+    NoWater,
+    Cost,
+}
+
+impl PlacementError {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            PlacementError::Locked => "LOCKED",
+            PlacementError::Slope => "STEEP",
+            PlacementError::Biome => "BIOME",
+            PlacementError::Overlap => "TAKEN",
+            PlacementError::Underwater => "WET",
+            PlacementError::NoWater => "DRY",
+            PlacementError::Cost => "COST",
+        }
+    }
+}
+
+/// The structure the build tool places, and whether it fits under the cursor.
+pub(crate) struct BuildPlan {
+    pub(crate) kind: StructureKind,
+    pub(crate) rotation: f32,
+    /// The planetoid and the point on it the structure would go.
+    pub(crate) target: Option<(Entity, Vec2)>,
+    pub(crate) error: Option<PlacementError>,
+}
+
+impl Default for BuildPlan {
+    fn default() -> Self {
+        Self {
+            kind: StructureKind::Shelter,
+            rotation: 0.0,
+            target: None,
+            error: None,
+        }
+    }
+}
+
+/// Follows the cursor with the planned structure while building.
+#[derive(Component)]
+struct Ghost;
+
+struct StructureAssets {
+    meshes: [Handle<Mesh>; StructureKind::ALL.len()],
+    materials: [Handle<StandardMaterial>; StructureKind::ALL.len()],
+    lit_materials: [Handle<StandardMaterial>; StructureKind::ALL.len()],
+    ghost_valid: Handle<StandardMaterial>,
+    ghost_invalid: Handle<StandardMaterial>,
+}
+
+fn setup_structure_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut flat = |color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            alpha_mode: if color.a() < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            },
+            unlit: true,
+            ..default()
+        })
+    };
+
+    commands.insert_resource(StructureAssets {
+        meshes: StructureKind::ALL.map(|kind| {
+            // standing on the ground rather than halfway in it
+            let size = kind.size();
+            meshes.add(Mesh::from(shape::Box {
+                min_x: -size.x / 2.0,
+                max_x: size.x / 2.0,
+                min_y: 0.0,
+                max_y: size.y,
+                min_z: -size.z / 2.0,
+                max_z: size.z / 2.0,
+            }))
+        }),
+        materials: StructureKind::ALL.map(|kind| flat(kind.color())),
+        lit_materials: StructureKind::ALL.map(|kind| flat(kind.lit_color())),
+        ghost_valid: flat(Color::rgba(0.0, 0.89, 0.21, 0.6)),
+        ghost_invalid: flat(Color::rgba(1.0, 0.0, 0.3, 0.6)),
+    });
+}
+
+fn reset_build_plan(mut plan: ResMut<BuildPlan>) {
+    *plan = BuildPlan::default();
+}
+
+fn spawn_ghost(
+    mut commands: Commands,
+    assets: Res<StructureAssets>,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+) {
+    commands
+        .spawn_bundle(MaterialMeshBundle {
+            mesh: assets.meshes[0].clone(),
+            material: assets.ghost_valid.clone(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(PlanetoidTransform::default())
+        .insert(Ghost)
+        .insert(InGame)
+        .insert(game_world_render_layer.0);
+}
+
+/// Tab picks the next unlocked kind of structure and R turns it, while the build tool is
+/// active.
+fn adjust_build_plan(
+    keys: Res<Input<KeyCode>>,
+    tool: Res<ActiveTool>,
+    unlocks: Res<Unlocks>,
+    mut plan: ResMut<BuildPlan>,
+) {
+    if tool.0 != Tool::Build {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Tab) {
+        let count = StructureKind::ALL.len();
+        let next = (1..=count)
+            .map(|offset| StructureKind::ALL[(plan.kind.index() + offset) % count])
+            .find(|kind| unlocks.structure(*kind));
+        if let Some(next) = next {
+            plan.kind = next;
+        }
+    }
+    if keys.just_pressed(KeyCode::R) {
+        plan.rotation = (plan.rotation + ROTATE_STEP) % (PI * 2.0);
+    }
+}
+
+/// Steepest rise of the ground around a point, in height per radian.
+fn slope_at(ground: &Heightfield, sphere_coords: Vec2) -> f32 {
+    let height = ground.height_at(sphere_coords);
+    [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y]
+        .into_iter()
+        .map(|direction| {
+            let neighbor = sphere_coords + direction * SLOPE_STEP;
+            let neighbor = Vec2::new(neighbor.x, neighbor.y.clamp(0.0, 1.0));
+            // longitude steps shrink towards the poles
+            let distance = great_circle_distance(sphere_coords, neighbor).max(1e-4);
+            (ground.height_at(neighbor) - height).abs() / distance
+        })
+        .fold(0.0, f32::max)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_placement(
+    pick: Res<CursorPick>,
+    tool: Res<ActiveTool>,
+    index: Res<SurfaceIndex>,
+    economy: Res<Economy>,
+    unlocks: Res<Unlocks>,
+    mut plan: ResMut<BuildPlan>,
+    planetoids: Query<(&Planetoid, &Heightfield, &Water)>,
+    structures: Query<(), With<Structure>>,
+) {
+    plan.target = pick
+        .planetoid
+        .zip(pick.surface)
+        .filter(|_| tool.0 == Tool::Build);
+    let (entity, target, (planetoid, ground, water)) = match plan.target {
+        Some((entity, target)) => match planetoids.get(entity) {
+            Ok(planetoid) => (entity, target, planetoid),
+            Err(_) => {
+                plan.error = None;
+                return;
+            }
+        },
+        None => {
+            plan.error = None;
+            return;
+        }
+    };
+
+    let kind = plan.kind;
+    plan.error = if !unlocks.structure(kind) {
+        Some(PlacementError::Locked)
+    } else if slope_at(ground, target) > MAX_SLOPE {
+        Some(PlacementError::Slope)
+    } else if water.depth_at(ground, target) > MAX_BUILD_DEPTH {
+        Some(PlacementError::Underwater)
+    } else if !kind.allows(biome_at(target, &planetoid.params)) {
+        Some(PlacementError::Biome)
+    } else if kind == StructureKind::Pump
+        && water.nearest_water(ground, target, PUMP_REACH).is_none()
+    {
+        Some(PlacementError::NoWater)
+    } else if index
+        .nearest(entity, target, FOOTPRINT * 2.0, |entity| {
+            structures.get(*entity).is_ok()
+        })
+        .is_some()
+    {
+        Some(PlacementError::Overlap)
+    } else if !economy.can_afford(&kind.cost()) {
+        Some(PlacementError::Cost)
+    } else {
+        None
+    };
+}
+
+fn place_structure(
+    buttons: Res<Input<MouseButton>>,
+    plan: Res<BuildPlan>,
+    mut economy: ResMut<Economy>,
+    mut place_events: EventWriter<PlaceStructure>,
+    mut built_events: EventWriter<StructureBuilt>,
+    planetoids: Query<&Planetoid>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) || plan.error.is_some() {
+        return;
+    }
+    let (planetoid, target) = match plan.target {
+        Some((entity, target)) => match planetoids.get(entity) {
+            Ok(planetoid) => (planetoid, target),
+            Err(_) => return,
+        },
+        None => return,
+    };
+
+    if economy.spend(&plan.kind.cost()) {
+        place_events.send(PlaceStructure {
+            kind: plan.kind,
+            planetoid: planetoid.index,
+            sphere_coords: target,
+            rotation: plan.rotation,
+        });
+        built_events.send(StructureBuilt { kind: plan.kind });
+    }
+}
+
+fn update_ghost(
+    mut commands: Commands,
+    plan: Res<BuildPlan>,
+    assets: Res<StructureAssets>,
+    mut ghost: Query<
+        (
+            Entity,
+            Option<&Parent>,
+            &mut PlanetoidTransform,
+            &mut Visibility,
+            &mut Handle<Mesh>,
+            &mut Handle<StandardMaterial>,
+        ),
+        With<Ghost>,
+    >,
+) {
+    let (entity, parent, mut transform, mut visibility, mut mesh, mut material) =
+        match ghost.get_single_mut() {
+            Ok(ghost) => ghost,
+            Err(_) => return,
+        };
+
+    visibility.is_visible = plan.target.is_some();
+    if let Some((planetoid, target)) = plan.target {
+        // the ghost follows the cursor from one planetoid to the next
+        if parent.map(|parent| parent.get()) != Some(planetoid) {
+            commands.entity(planetoid).add_child(entity);
+        }
+        transform.sphere_coords = target;
+        transform.rotation = plan.rotation;
+        *mesh = assets.meshes[plan.kind.index()].clone();
+        *material = if plan.error.is_none() {
+            assets.ghost_valid.clone()
+        } else {
+            assets.ghost_invalid.clone()
+        };
+    }
+}
+
+fn spawn_structures(
+    mut commands: Commands,
+    mut place_events: EventReader<PlaceStructure>,
+    mut pending: Local<Vec<PlaceStructure>>,
+    assets: Res<StructureAssets>,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    planetoids: Query<(Entity, &Planetoid)>,
+) {
+    pending.extend(place_events.iter().cloned());
+    // restored structures arrive before the planetoids they stand on are set up
+    if pending.is_empty() || planetoids.is_empty() {
+        return;
+    }
+
+    for event in pending.drain(..) {
+        let planetoid = match planetoids
+            .iter()
+            .find(|(_, planetoid)| planetoid.index == event.planetoid)
+        {
+            Some((entity, _)) => entity,
+            None => {
+                bevy::log::warn!(
+                    "cannot place structure on missing planetoid {}",
+                    event.planetoid
+                );
+                continue;
+            }
+        };
+        let structure = commands
+            .spawn_bundle(MaterialMeshBundle {
+                mesh: assets.meshes[event.kind.index()].clone(),
+                material: assets.materials[event.kind.index()].clone(),
+                ..default()
+            })
+            .insert(PlanetoidTransform {
+                sphere_coords: event.sphere_coords,
+                rotation: event.rotation,
+                scale: 1.0,
+            })
+            .insert(Structure { kind: event.kind })
+            .insert(InGame)
+            .insert(game_world_render_layer.0)
+            .id();
+        commands.entity(planetoid).add_child(structure);
+    }
+}
+
+fn run_structures(
+    time: Res<Time>,
+    index: Res<SurfaceIndex>,
+    mut economy: ResMut<Economy>,
+    planetoids: Query<(&Heightfield, &Water)>,
+    structures: Query<(&Structure, &PlanetoidTransform, &Parent)>,
+    mut creatures: Query<&mut Needs, With<Creature>>,
+    mut plants: Query<&mut Plant>,
+) {
+    let days = time.delta_seconds() / DAY_LENGTH;
+
+    for (structure, transform, parent) in structures.iter() {
+        let kind = structure.kind;
+        if kind == StructureKind::Pump {
+            // the water a pump was built by can dry up or drain away
+            let has_water = planetoids
+                .get(parent.get())
+                .map_or(false, |(ground, water)| {
+                    water
+                        .nearest_water(ground, transform.sphere_coords, PUMP_REACH)
+                        .is_some()
+                });
+            if !has_water {
+                continue;
+            }
+            economy.earn(&Supplies {
+                water: PUMP_WATER_PER_DAY * days,
+                ..Supplies::NONE
+            });
+            continue;
+        }
+
+        for (entity, _) in index.within(parent.get(), transform.sphere_coords, kind.radius()) {
+            match kind {
+                StructureKind::Shelter => {
+                    if let Ok(mut needs) = creatures.get_mut(entity) {
+                        needs.energy = (needs.energy + SHELTER_ENERGY_PER_DAY * days).min(1.0);
+                    }
+                }
+                StructureKind::Feeder => {
+                    if let Ok(mut needs) = creatures.get_mut(entity) {
+                        let hunger = (FEEDER_HUNGER_PER_DAY * days).min(1.0 - needs.hunger);
+                        let cost = Supplies {
+                            nutrients: hunger * FEEDER_NUTRIENTS_PER_HUNGER,
+                            ..Supplies::NONE
+                        };
+                        if economy.spend(&cost) {
+                            needs.hunger += hunger;
+                        }
+                    }
+                }
+                StructureKind::Sprinkler => {
+                    if let Ok(mut plant) = plants.get_mut(entity) {
+                        let health = (SPRINKLER_HEALTH_PER_DAY * days).min(1.0 - plant.health);
+                        let cost = Supplies {
+                            water: health * SPRINKLER_WATER_PER_HEALTH,
+                            ..Supplies::NONE
+                        };
+                        if economy.spend(&cost) {
+                            plant.health += health;
+                        }
+                    }
+                }
+                StructureKind::Pump => {}
+            }
+        }
+    }
+}
+
+/// Turns the lights of structures on while they are on the night side.
+fn light_structures(
+    clock: Res<GameClock>,
+    assets: Res<StructureAssets>,
+    planetoids: Query<&Transform, With<Planetoid>>,
+    mut structures: Query<(
+        &Structure,
+        &PlanetoidTransform,
+        &Parent,
+        &mut Handle<StandardMaterial>,
+    )>,
+) {
+    let sun = clock.sun_direction();
+    for (structure, transform, parent, mut material) in structures.iter_mut() {
+        let night = planetoids.get(parent.get()).map_or(false, |planetoid| {
+            !in_daylight(planetoid, transform.sphere_coords, sun)
+        });
+        let wanted = if night {
+            &assets.lit_materials[structure.kind.index()]
+        } else {
+            &assets.materials[structure.kind.index()]
+        };
+        if *material != *wanted {
+            *material = wanted.clone();
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    creature::Needs,
+    economy::{Economy, Supplies},
+    ecosystem::{Corpse, Soil},
+    objectives::Unlocks,
+    planetoid::{
+        heightfield::{TerrainBrush, Terraform},
+        transform::{PlanetoidTransform, SurfaceIndex},
+        Planetoid,
+    },
+    plant::{Plant, SpawnPlant},
+    selection::CursorPick,
+    state::AppState,
+};
+
+/// Radians around the clicked point the watering can and food dispenser reach.
+const SPREAD_RADIUS: f32 = 0.15;
+/// Radians around the clicked point the shovel digs up something in.
+const DIG_RADIUS: f32 = 0.06;
+/// Radians around the clicked point the terrain brushes reach.
+const TERRAFORM_RADIUS: f32 = 0.2;
+/// Height change at the center of the brush when raising or lowering.
+const TERRAFORM_AMOUNT: f32 = 0.02;
+/// Fraction of the way to flat the smoothing brush goes at its center.
+const SMOOTH_AMOUNT: f32 = 0.5;
+const WATER_THIRST: f32 = 0.5;
+const WATER_PLANT_HEALTH: f32 = 0.3;
+const FEED_HUNGER: f32 = 0.4;
+/// Fertility added where a corpse is buried, per unit of meat.
+const BURY_FERTILITY: f32 = 0.8;
+
+pub(crate) struct ToolPlugin;
+
+impl Plugin for ToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveTool>()
+            .init_resource::<ToolCooldowns>()
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_tools))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(select_tool)
+                    .with_system(tick_cooldowns)
+                    .with_system(use_tool.after(select_tool).after(tick_cooldowns)),
+            );
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Tool {
+    /// Sends the selected creature, or everyone, to the clicked point.
+    Command,
+    WaterCan,
+    SeedBag,
+    FoodDispenser,
+    RaiseTerrain,
+    LowerTerrain,
+    SmoothTerrain,
+    Shovel,
+    /// Places the structure picked in the `BuildPlan`, which also decides what it costs.
+    Build,
+}
+
+impl Tool {
+    pub(crate) const ALL: [Tool; 9] = [
+        Tool::Command,
+        Tool::WaterCan,
+        Tool::SeedBag,
+        Tool::FoodDispenser,
+        Tool::RaiseTerrain,
+        Tool::LowerTerrain,
+        Tool::SmoothTerrain,
+        Tool::Shovel,
+        Tool::Build,
+    ];
+
+    /// Short name that fits the HUD.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Tool::Command => "MOVE",
+            Tool::WaterCan => "WATER",
+            Tool::SeedBag => "SEED",
+            Tool::FoodDispenser => "FEED",
+            Tool::RaiseTerrain => "RAISE",
+            Tool::LowerTerrain => "LOWER",
+            Tool::SmoothTerrain => "SMOOTH",
+            Tool::Shovel => "DIG",
+            Tool::Build => "BUILD",
+        }
+    }
+
+    /// Seconds before the tool can be used again.
+    pub(crate) fn cooldown(self) -> f32 {
+        match self {
+            Tool::Command | Tool::Build => 0.0,
+            Tool::WaterCan => 1.0,
+            Tool::SeedBag => 0.5,
+            Tool::FoodDispenser => 2.0,
+            Tool::RaiseTerrain | Tool::LowerTerrain | Tool::SmoothTerrain => 3.0,
+            Tool::Shovel => 1.0,
+        }
+    }
+
+    pub(crate) fn cost(self) -> Supplies {
+        match self {
+            Tool::WaterCan => Supplies {
+                water: 1.0,
+                ..Supplies::NONE
+            },
+            Tool::SeedBag => Supplies {
+                seeds: 1,
+                ..Supplies::NONE
+            },
+            Tool::FoodDispenser => Supplies {
+                nutrients: 1.0,
+                ..Supplies::NONE
+            },
+            Tool::RaiseTerrain | Tool::LowerTerrain | Tool::SmoothTerrain => Supplies {
+                nutrients: 2.0,
+                care: 5.0,
+                ..Supplies::NONE
+            },
+            Tool::Command | Tool::Shovel | Tool::Build => Supplies::NONE,
+        }
+    }
+
+    fn index(self) -> usize {
+        Tool::ALL.iter().position(|tool| *tool == self).unwrap()
+    }
+}
+
+pub(crate) struct ActiveTool(pub(crate) Tool);
+
+impl Default for ActiveTool {
+    fn default() -> Self {
+        Self(Tool::Command)
+    }
+}
+
+/// Seconds until each tool, in the order of `Tool::ALL`, can be used again.
+#[derive(Default)]
+pub(crate) struct ToolCooldowns([f32; Tool::ALL.len()]);
+
+impl ToolCooldowns {
+    pub(crate) fn remaining(&self, tool: Tool) -> f32 {
+        self.0[tool.index()]
+    }
+
+    /// How far along the cooldown of the tool is, from 0.0 just used to 1.0 ready.
+    pub(crate) fn progress(&self, tool: Tool) -> f32 {
+        if tool.cooldown() <= 0.0 {
+            1.0
+        } else {
+            1.0 - self.remaining(tool) / tool.cooldown()
+        }
+    }
+}
+
+fn reset_tools(mut tool: ResMut<ActiveTool>, mut cooldowns: ResMut<ToolCooldowns>) {
+    *tool = ActiveTool::default();
+    *cooldowns = ToolCooldowns::default();
+}
+
+fn select_tool(keys: Res<Input<KeyCode>>, unlocks: Res<Unlocks>, mut tool: ResMut<ActiveTool>) {
+    const KEYS: [KeyCode; Tool::ALL.len()] = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+        KeyCode::Key7,
+        KeyCode::Key8,
+        KeyCode::Key9,
+    ];
+
+    for (key, selected) in KEYS.into_iter().zip(Tool::ALL) {
+        if keys.just_pressed(key) && unlocks.tool(selected) {
+            tool.0 = selected;
+        }
+    }
+}
+
+fn tick_cooldowns(time: Res<Time>, mut cooldowns: ResMut<ToolCooldowns>) {
+    for remaining in cooldowns.0.iter_mut() {
+        *remaining = (*remaining - time.delta_seconds()).max(0.0);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn use_tool(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    pick: Res<CursorPick>,
+    index: Res<SurfaceIndex>,
+    tool: Res<ActiveTool>,
+    mut cooldowns: ResMut<ToolCooldowns>,
+    mut economy: ResMut<Economy>,
+    mut plant_events: EventWriter<SpawnPlant>,
+    mut terraform_events: EventWriter<Terraform>,
+    mut soil: Query<&mut Soil, With<Planetoid>>,
+    mut needs: Query<&mut Needs>,
+    mut plants: Query<&mut Plant>,
+    corpses: Query<&Corpse>,
+    positions: Query<&PlanetoidTransform>,
+) {
+    // moving creatures around is handled with the rest of the creature commands, and
+    // building with the rest of the structures
+    if matches!(tool.0, Tool::Command | Tool::Build) {
+        return;
+    }
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (planetoid, surface) = match (pick.planetoid, pick.surface) {
+        (Some(planetoid), Some(surface)) => (planetoid, surface),
+        _ => return,
+    };
+    if cooldowns.remaining(tool.0) > 0.0 || !economy.spend(&tool.0.cost()) {
+        return;
+    }
+
+    match tool.0 {
+        Tool::Command | Tool::Build => {}
+        Tool::WaterCan => {
+            for (entity, _) in index.within(planetoid, surface, SPREAD_RADIUS) {
+                if let Ok(mut needs) = needs.get_mut(entity) {
+                    needs.thirst = (needs.thirst + WATER_THIRST).min(1.0);
+                }
+                if let Ok(mut plant) = plants.get_mut(entity) {
+                    plant.health = (plant.health + WATER_PLANT_HEALTH).min(1.0);
+                }
+            }
+        }
+        Tool::SeedBag => {
+            plant_events.send(SpawnPlant {
+                planetoid,
+                sphere_coords: surface,
+            });
+        }
+        Tool::FoodDispenser => {
+            for (entity, _) in index.within(planetoid, surface, SPREAD_RADIUS) {
+                if let Ok(mut needs) = needs.get_mut(entity) {
+                    needs.hunger = (needs.hunger + FEED_HUNGER).min(1.0);
+                }
+            }
+        }
+        Tool::RaiseTerrain | Tool::LowerTerrain | Tool::SmoothTerrain => {
+            let (brush, strength) = match tool.0 {
+                Tool::RaiseTerrain => (TerrainBrush::Raise, TERRAFORM_AMOUNT),
+                Tool::LowerTerrain => (TerrainBrush::Lower, TERRAFORM_AMOUNT),
+                _ => (TerrainBrush::Smooth, SMOOTH_AMOUNT),
+            };
+            terraform_events.send(Terraform {
+                planetoid,
+                sphere_coords: surface,
+                radius: TERRAFORM_RADIUS,
+                strength,
+                brush,
+            });
+        }
+        Tool::Shovel => {
+            let dug = index.nearest(planetoid, surface, DIG_RADIUS, |entity| {
+                plants.get(*entity).is_ok() || corpses.get(*entity).is_ok()
+            });
+            let (entity, _) = match dug {
+                Some(dug) => dug,
+                None => return,
+            };
+
+            // grown plants are dug up with their seeds, corpses are buried to feed the soil
+            if let Ok(plant) = plants.get(entity) {
+                if plant.growth >= 1.0 {
+                    economy.earn(&Supplies {
+                        seeds: 1,
+                        ..Supplies::NONE
+                    });
+                }
+            } else if let (Ok(corpse), Ok(position), Ok(mut soil)) = (
+                corpses.get(entity),
+                positions.get(entity),
+                soil.get_mut(planetoid),
+            ) {
+                let fertility = soil.fertility.get_mut(position.sphere_coords);
+                *fertility = (*fertility + corpse.meat * BURY_FERTILITY).min(1.0);
+            }
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    // a shovel that found nothing to dig returns early, and is ready again straight away
+    cooldowns.0[tool.0.index()] = tool.0.cooldown();
+}
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    camera::CameraFocus,
+    creature::{Creature, Goal},
+    economy::{Economy, Supplies},
+    hud::{Announcement, HUD_BAD, HUD_GOOD},
+    planetoid::{transform::PlanetoidTransform, Planetoid},
+    plant::SpawnPlant,
+    selection::Selected,
+    state::{AppState, WorldRng},
+};
+
+/// Care points it takes to send a creature over to another planetoid.
+const TRAVEL_CARE: f32 = 25.0;
+/// Seeds in a packet sent over to another planetoid, each sown where it lands.
+const SEED_PACKET: u32 = 5;
+/// Care points it takes to send a packet of seeds, cheaper than a creature.
+const SEED_PACKET_CARE: f32 = 5.0;
+
+pub(crate) struct TravelPlugin;
+
+impl Plugin for TravelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(send_creature)
+                .with_system(send_seeds),
+        );
+    }
+}
+
+/// T sends the selected creature to the planetoid the camera is looking at, landing
+/// somewhere away from the poles.
+#[allow(clippy::too_many_arguments)]
+fn send_creature(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    focus: Res<CameraFocus>,
+    mut rng: ResMut<WorldRng>,
+    mut economy: ResMut<Economy>,
+    mut announcements: EventWriter<Announcement>,
+    planetoids: Query<&Planetoid>,
+    mut selected: Query<
+        (Entity, &Parent, &mut PlanetoidTransform, &mut Goal),
+        (With<Creature>, With<Selected>),
+    >,
+) {
+    if !keys.just_pressed(KeyCode::T) || focus.system_view {
+        return;
+    }
+    let destination = match focus.planetoid {
+        Some(destination) => destination,
+        None => return,
+    };
+
+    for (entity, parent, mut transform, mut goal) in selected.iter_mut() {
+        if parent.get() == destination {
+            continue;
+        }
+        let index = match planetoids.get(destination) {
+            Ok(planetoid) => planetoid.index,
+            Err(_) => return,
+        };
+
+        let cost = Supplies {
+            care: TRAVEL_CARE,
+            ..Supplies::NONE
+        };
+        if !economy.spend(&cost) {
+            announcements.send(Announcement {
+                title: "TRAVEL".to_string(),
+                text: "NEEDS CARE".to_string(),
+                color: HUD_BAD,
+            });
+            return;
+        }
+
+        commands.entity(destination).add_child(entity);
+        transform.sphere_coords = Vec2::new(rng.0.gen_range(0.0..1.0), rng.0.gen_range(0.2..0.8));
+        *goal = Goal::default();
+        announcements.send(Announcement {
+            title: "TRAVEL".to_string(),
+            text: format!("TO WORLD {}", index + 1),
+            color: HUD_GOOD,
+        });
+    }
+}
+
+/// T with no creature selected sends a packet of seeds to the planetoid the camera is
+/// looking at, scattered away from the poles. The seed stock itself is shared by the whole
+/// system, but sowing with the seed bag only reaches the planetoid under the cursor.
+#[allow(clippy::too_many_arguments)]
+fn send_seeds(
+    keys: Res<Input<KeyCode>>,
+    focus: Res<CameraFocus>,
+    mut rng: ResMut<WorldRng>,
+    mut economy: ResMut<Economy>,
+    mut announcements: EventWriter<Announcement>,
+    mut plant_events: EventWriter<SpawnPlant>,
+    planetoids: Query<&Planetoid>,
+    selected: Query<(), (With<Creature>, With<Selected>)>,
+) {
+    if !keys.just_pressed(KeyCode::T) || focus.system_view || !selected.is_empty() {
+        return;
+    }
+    let destination = match focus.planetoid {
+        Some(destination) => destination,
+        None => return,
+    };
+    let index = match planetoids.get(destination) {
+        Ok(planetoid) => planetoid.index,
+        Err(_) => return,
+    };
+
+    let cost = Supplies {
+        seeds: SEED_PACKET,
+        care: SEED_PACKET_CARE,
+        ..Supplies::NONE
+    };
+    if !economy.spend(&cost) {
+        announcements.send(Announcement {
+            title: "SEEDS".to_string(),
+            text: "NOT ENOUGH".to_string(),
+            color: HUD_BAD,
+        });
+        return;
+    }
+
+    for _ in 0..SEED_PACKET {
+        plant_events.send(SpawnPlant {
+            planetoid: destination,
+            sphere_coords: Vec2::new(rng.0.gen_range(0.0..1.0), rng.0.gen_range(0.2..0.8)),
+        });
+    }
+    announcements.send(Announcement {
+        title: "SEEDS".to_string(),
+        text: format!("TO WORLD {}", index + 1),
+        color: HUD_GOOD,
+    });
+}
@@ -0,0 +1,343 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    clock::{GameClock, Season, DAY_LENGTH},
+    creature::{Creature, Needs},
+    hud::{Announcement, HUD_BAD, HUD_GOOD, HUD_WARN},
+    planetoid::{
+        heightfield::{Terraform, TerrainBrush},
+        in_daylight,
+        transform::{great_circle_distance, PlanetoidTransform},
+        water::Water,
+        Planetoid,
+    },
+    plant::Plant,
+    state::{AppState, WorldParams, WorldRng},
+};
+
+/// Nothing happens on the first day, so a new world gets to settle first.
+const QUIET_DAYS: u32 = 1;
+/// Impacts in a single meteor shower.
+const METEORS: Range<u32> = 3..7;
+/// Crater radius in radians, and how deep the crater is dug at its center.
+const CRATER_RADIUS: Range<f32> = 0.04..0.08;
+const CRATER_DEPTH: Range<f32> = 0.015..0.03;
+/// Growth a bloom gives every plant on top of healing it.
+const BLOOM_GROWTH: f32 = 0.3;
+/// Needs a plague takes from every creature per day.
+const PLAGUE_DRAIN_PER_DAY: f32 = 0.2;
+/// Creatures it takes for a plague to be as likely as it gets.
+const PLAGUE_CROWD: f32 = 30.0;
+/// Plant health and creature energy a solar flare burns on the day side.
+const FLARE_SCORCH: f32 = 0.5;
+const FLARE_EXHAUSTION: f32 = 0.4;
+
+pub(crate) struct WorldEventPlugin;
+
+impl Plugin for WorldEventPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveEvents>()
+            .add_event::<WorldEventStarted>()
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_events))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(schedule_events)
+                    .with_system(meteor_shower.after(schedule_events))
+                    .with_system(bloom.after(schedule_events))
+                    .with_system(solar_flare.after(schedule_events))
+                    .with_system(drought.after(schedule_events))
+                    .with_system(plague.after(schedule_events)),
+            );
+    }
+}
+
+/// Something that happens to one planetoid of the system, picked when the event starts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum WorldEvent {
+    /// Meteors dig craters and flatten the plants where they land.
+    MeteorShower,
+    /// No rain falls while it lasts.
+    Drought,
+    /// Every plant heals and shoots up at once.
+    Bloom,
+    /// Creatures fall sick and waste away while it lasts.
+    Plague,
+    /// Scorches plants and exhausts creatures on the side facing the sun.
+    SolarFlare,
+}
+
+impl WorldEvent {
+    const ALL: [WorldEvent; 5] = [
+        WorldEvent::MeteorShower,
+        WorldEvent::Drought,
+        WorldEvent::Bloom,
+        WorldEvent::Plague,
+        WorldEvent::SolarFlare,
+    ];
+
+    fn chance_per_day(self) -> f32 {
+        match self {
+            WorldEvent::MeteorShower => 0.08,
+            WorldEvent::Drought => 0.06,
+            WorldEvent::Bloom => 0.08,
+            WorldEvent::Plague => 0.05,
+            WorldEvent::SolarFlare => 0.05,
+        }
+    }
+
+    fn season_factor(self, season: Season) -> f32 {
+        match (self, season) {
+            (WorldEvent::MeteorShower, Season::Autumn) => 2.0,
+            (WorldEvent::Drought, Season::Summer) => 3.0,
+            (WorldEvent::Drought, Season::Winter) => 0.2,
+            (WorldEvent::Bloom, Season::Spring) => 3.0,
+            (WorldEvent::Bloom, Season::Winter) => 0.0,
+            (WorldEvent::Plague, Season::Winter) => 2.5,
+            (WorldEvent::SolarFlare, Season::Summer) => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    fn world_factor(self, params: &WorldParams) -> f32 {
+        match self {
+            // slow spinning worlds bake in the sun for longer
+            WorldEvent::Drought | WorldEvent::SolarFlare => {
+                (WorldParams::default().rotation_speed / params.rotation_speed).sqrt()
+            }
+            // flat lowlands hold on to their soil, rugged ones have it washed away
+            WorldEvent::Bloom => (WorldParams::default().terrain_amplitude
+                / params.terrain_amplitude)
+                .clamp(0.5, 2.0),
+            WorldEvent::MeteorShower | WorldEvent::Plague => 1.0,
+        }
+    }
+
+    /// Days the event lasts, or nothing if it's over as soon as it happens.
+    fn duration(self) -> Option<f32> {
+        match self {
+            WorldEvent::Drought => Some(3.0),
+            WorldEvent::Plague => Some(2.0),
+            WorldEvent::MeteorShower | WorldEvent::Bloom | WorldEvent::SolarFlare => None,
+        }
+    }
+
+    fn announcement(self) -> Announcement {
+        let (title, text, color) = match self {
+            WorldEvent::MeteorShower => ("METEOR SHOWER", "CRATERS FORM", HUD_WARN),
+            WorldEvent::Drought => ("DROUGHT", "NO RAIN", HUD_WARN),
+            WorldEvent::Bloom => ("BLOOM", "PLANTS THRIVE", HUD_GOOD),
+            WorldEvent::Plague => ("PLAGUE", "CREATURES SICK", HUD_BAD),
+            WorldEvent::SolarFlare => ("SOLAR FLARE", "DAY SIDE BURNS", HUD_BAD),
+        };
+        Announcement {
+            title: title.to_string(),
+            text: text.to_string(),
+            color,
+        }
+    }
+}
+
+/// Sent when a world event begins on a planetoid.
+pub(crate) struct WorldEventStarted {
+    pub(crate) event: WorldEvent,
+    pub(crate) planetoid: Entity,
+}
+
+/// Events that last a while, with the planetoid each is on and the days it has left.
+#[derive(Default)]
+pub(crate) struct ActiveEvents(Vec<(WorldEvent, Entity, f32)>);
+
+impl ActiveEvents {
+    pub(crate) fn contains(&self, event: WorldEvent) -> bool {
+        self.0.iter().any(|(active, ..)| *active == event)
+    }
+
+    pub(crate) fn on(&self, event: WorldEvent, planetoid: Entity) -> bool {
+        self.0
+            .iter()
+            .any(|(active, on, _)| *active == event && *on == planetoid)
+    }
+}
+
+fn reset_events(mut active: ResMut<ActiveEvents>) {
+    *active = ActiveEvents::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn schedule_events(
+    time: Res<Time>,
+    clock: Res<GameClock>,
+    mut rng: ResMut<WorldRng>,
+    mut active: ResMut<ActiveEvents>,
+    mut started_events: EventWriter<WorldEventStarted>,
+    mut announcements: EventWriter<Announcement>,
+    planetoids: Query<(Entity, &Planetoid)>,
+    creatures: Query<&Parent, With<Creature>>,
+) {
+    let days = time.delta_seconds() / DAY_LENGTH;
+    for (.., left) in active.0.iter_mut() {
+        *left -= days;
+    }
+    active.0.retain(|(.., left)| *left > 0.0);
+
+    let planetoids: Vec<(Entity, &Planetoid)> = planetoids.iter().collect();
+    if clock.day() < QUIET_DAYS || planetoids.is_empty() {
+        return;
+    }
+
+    for event in WorldEvent::ALL {
+        if active.contains(event) {
+            continue;
+        }
+
+        // the event is weighed against the world it would strike
+        let (target, planetoid) = planetoids[rng.0.gen_range(0..planetoids.len())];
+        let mut chance = event.chance_per_day()
+            * event.season_factor(clock.season())
+            * event.world_factor(&planetoid.params);
+        if event == WorldEvent::Plague {
+            // plagues need a crowd to spread through
+            let crowd = creatures
+                .iter()
+                .filter(|parent| parent.get() == target)
+                .count();
+            chance *= (crowd as f32 / PLAGUE_CROWD).min(1.0);
+        }
+        if !rng.0.gen_bool((chance * days).clamp(0.0, 1.0) as f64) {
+            continue;
+        }
+
+        bevy::log::info!(
+            "{:?} on planetoid {} on day {}",
+            event,
+            planetoid.index,
+            clock.day() + 1
+        );
+        if let Some(duration) = event.duration() {
+            active.0.push((event, target, duration));
+        }
+        started_events.send(WorldEventStarted {
+            event,
+            planetoid: target,
+        });
+        announcements.send(event.announcement());
+    }
+}
+
+fn meteor_shower(
+    mut rng: ResMut<WorldRng>,
+    mut started_events: EventReader<WorldEventStarted>,
+    mut terraform_events: EventWriter<Terraform>,
+    mut plants: Query<(&mut Plant, &PlanetoidTransform, &Parent)>,
+) {
+    for started in started_events
+        .iter()
+        .filter(|started| started.event == WorldEvent::MeteorShower)
+    {
+        let planetoid = started.planetoid;
+        for _ in 0..rng.0.gen_range(METEORS) {
+            let impact = Terraform {
+                planetoid,
+                sphere_coords: Vec2::new(rng.0.gen_range(0.0..1.0), rng.0.gen_range(0.1..0.9)),
+                radius: rng.0.gen_range(CRATER_RADIUS),
+                strength: rng.0.gen_range(CRATER_DEPTH),
+                brush: TerrainBrush::Lower,
+            };
+
+            // plants in the crater wither away
+            for (mut plant, transform, parent) in plants.iter_mut() {
+                if parent.get() == planetoid
+                    && great_circle_distance(transform.sphere_coords, impact.sphere_coords)
+                        < impact.radius
+                {
+                    plant.growth = 0.0;
+                }
+            }
+            terraform_events.send(impact);
+        }
+    }
+}
+
+fn bloom(
+    mut started_events: EventReader<WorldEventStarted>,
+    mut plants: Query<(&mut Plant, &Parent)>,
+) {
+    for started in started_events
+        .iter()
+        .filter(|started| started.event == WorldEvent::Bloom)
+    {
+        for (mut plant, _) in plants
+            .iter_mut()
+            .filter(|(_, parent)| parent.get() == started.planetoid)
+        {
+            plant.health = 1.0;
+            plant.growth = (plant.growth + BLOOM_GROWTH).min(1.0);
+        }
+    }
+}
+
+fn solar_flare(
+    clock: Res<GameClock>,
+    mut started_events: EventReader<WorldEventStarted>,
+    planetoids: Query<&Transform, With<Planetoid>>,
+    mut plants: Query<(&mut Plant, &PlanetoidTransform, &Parent)>,
+    mut creatures: Query<(&mut Needs, &PlanetoidTransform, &Parent), With<Creature>>,
+) {
+    let sun = clock.sun_direction();
+    let daylit = |transform: &PlanetoidTransform, parent: &Parent| {
+        planetoids.get(parent.get()).map_or(false, |planetoid| {
+            in_daylight(planetoid, transform.sphere_coords, sun)
+        })
+    };
+
+    for started in started_events
+        .iter()
+        .filter(|started| started.event == WorldEvent::SolarFlare)
+    {
+        for (mut plant, transform, parent) in plants.iter_mut() {
+            if parent.get() == started.planetoid && daylit(transform, parent) {
+                plant.health = (plant.health - FLARE_SCORCH).max(0.0);
+            }
+        }
+        for (mut needs, transform, parent) in creatures.iter_mut() {
+            if parent.get() == started.planetoid && daylit(transform, parent) {
+                needs.energy = (needs.energy - FLARE_EXHAUSTION).max(0.0);
+            }
+        }
+    }
+}
+
+fn drought(
+    active: Res<ActiveEvents>,
+    mut planetoids: Query<(Entity, &mut Water), With<Planetoid>>,
+) {
+    for (planetoid, mut water) in planetoids.iter_mut() {
+        water.rain_scale = if active.on(WorldEvent::Drought, planetoid) {
+            0.0
+        } else {
+            1.0
+        };
+    }
+}
+
+fn plague(
+    time: Res<Time>,
+    active: Res<ActiveEvents>,
+    mut creatures: Query<(&mut Needs, &Parent), With<Creature>>,
+) {
+    if !active.contains(WorldEvent::Plague) {
+        return;
+    }
+
+    let drain = PLAGUE_DRAIN_PER_DAY * time.delta_seconds() / DAY_LENGTH;
+    for (mut needs, _) in creatures
+        .iter_mut()
+        .filter(|(_, parent)| active.on(WorldEvent::Plague, parent.get()))
+    {
+        needs.hunger = (needs.hunger - drain).max(0.0);
+        needs.energy = (needs.energy - drain).max(0.0);
+    }
+}